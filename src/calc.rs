@@ -0,0 +1,243 @@
+use crate::error::{bail, ensure, Result};
+use crate::DdddocrError;
+
+/// Symbols an arithmetic captcha can contain; used to restrict the OCR charset.
+pub const CALC_CHARSET: &str = "0123456789+-*/×÷xX=?？()（）＋－＝\
+    零〇一二三四五六七八九十百千两壹贰叁肆伍陆柒捌玖拾佰仟\
+    加减乘除以等于是多少";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CalcResult {
+    /// Raw OCR output.
+    pub text: String,
+    /// Normalized ASCII expression that was evaluated, e.g. `3*5`.
+    pub expression: String,
+    pub value: f64,
+}
+
+impl CalcResult {
+    /// The value as a captcha answer: integers without a fractional part.
+    pub fn answer(&self) -> String {
+        if self.value.fract() == 0.0 && self.value.abs() < 1e15 {
+            format!("{}", self.value as i64)
+        } else {
+            format!("{}", self.value)
+        }
+    }
+}
+
+fn chinese_digit(c: char) -> Option<u64> {
+    Some(match c {
+        '零' | '〇' => 0,
+        '一' | '壹' => 1,
+        '二' | '两' | '贰' => 2,
+        '三' | '叁' => 3,
+        '四' | '肆' => 4,
+        '五' | '伍' => 5,
+        '六' | '陆' => 6,
+        '七' | '柒' => 7,
+        '八' | '捌' => 8,
+        '九' | '玖' => 9,
+        _ => return None,
+    })
+}
+
+fn chinese_unit(c: char) -> Option<u64> {
+    Some(match c {
+        '十' | '拾' => 10,
+        '百' | '佰' => 100,
+        '千' | '仟' => 1000,
+        _ => return None,
+    })
+}
+
+/// Parses a run of Chinese numerals such as `十二`, `三百零五`, `一百二` or
+/// `二五`. `None` if it is malformed or does not fit in a `u64`.
+fn parse_chinese_number(s: &str) -> Option<u64> {
+    if !s.chars().any(|c| chinese_unit(c).is_some()) {
+        // Digit by digit, e.g. 二五 -> 25.
+        return s.chars().try_fold(0u64, |acc, c| acc.checked_mul(10)?.checked_add(chinese_digit(c)?));
+    }
+    let mut total = 0u64;
+    let mut current = None;
+    let mut last_unit = 1;
+    let mut skipped = false;
+    for c in s.chars() {
+        if let Some(d) = chinese_digit(c) {
+            // 零 marks a skipped place: 三百零五 = 305.
+            skipped |= d == 0;
+            current = Some(d);
+        } else {
+            let unit = chinese_unit(c)?;
+            // A leading unit has an implicit one: 十二 = 12.
+            total = total.checked_add(current.take().unwrap_or(1).checked_mul(unit)?)?;
+            last_unit = unit;
+            skipped = false;
+        }
+    }
+    // A bare digit after a unit fills the next place down: 一百二 = 120.
+    let tail = match current {
+        Some(d) if !skipped => d.checked_mul(last_unit / 10)?,
+        Some(d) => d,
+        None => 0,
+    };
+    total.checked_add(tail)
+}
+
+/// Rewrites OCR output into an ASCII expression, dropping the `=?` tail.
+pub fn normalize_expression(text: &str) -> Result<String> {
+    let text = text.replace("乘以", "*").replace("除以", "/");
+    let mut out = String::new();
+    let mut numeral = String::new();
+
+    let flush = |numeral: &mut String, out: &mut String| -> Result<()> {
+        if numeral == "一" && out.ends_with(|c: char| c.is_ascii_digit()) {
+            // A lone 一 right after a digit is a misread minus sign.
+            out.push('-');
+            numeral.clear();
+        } else if !numeral.is_empty() {
+            let n = parse_chinese_number(numeral)
+                .ok_or_else(|| DdddocrError::Unrecognized(format!("invalid Chinese numeral: {}", numeral)))?;
+            out.push_str(&n.to_string());
+            numeral.clear();
+        }
+        Ok(())
+    };
+
+    for c in text.chars() {
+        if chinese_digit(c).is_some() || chinese_unit(c).is_some() {
+            numeral.push(c);
+            continue;
+        }
+        flush(&mut numeral, &mut out)?;
+        match c {
+            '0'..='9' | '.' => out.push(c),
+            '+' | '＋' | '加' => out.push('+'),
+            '-' | '－' | '—' | '减' => out.push('-'),
+            '*' | '×' | 'x' | 'X' | '乘' => out.push('*'),
+            '/' | '÷' | '除' => out.push('/'),
+            '(' | '（' => out.push('('),
+            ')' | '）' => out.push(')'),
+            '=' | '＝' | '等' | '?' | '？' | '是' | '多' => break,
+            c if c.is_whitespace() || matches!(c, '于' | '少' | '以') => {}
+            c => bail!(Unrecognized, "unexpected symbol in expression: {}", c),
+        }
+    }
+    flush(&mut numeral, &mut out)?;
+    ensure!(!out.is_empty(), Unrecognized, "empty expression");
+    Ok(out)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        while let Some(&op) = self.chars.peek() {
+            match op {
+                '+' => { self.chars.next(); value += self.term()?; }
+                '-' => { self.chars.next(); value -= self.term()?; }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.factor()?;
+        while let Some(&op) = self.chars.peek() {
+            match op {
+                '*' => { self.chars.next(); value *= self.factor()?; }
+                '/' => {
+                    self.chars.next();
+                    let rhs = self.factor()?;
+                    ensure!(rhs != 0.0, Unrecognized, "division by zero");
+                    value /= rhs;
+                }
+                _ => break,
+            }
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<f64> {
+        match self.chars.peek() {
+            Some('-') => { self.chars.next(); Ok(-self.factor()?) }
+            Some('(') => {
+                self.chars.next();
+                let value = self.expr()?;
+                ensure!(self.chars.next() == Some(')'), Unrecognized, "unbalanced parenthesis");
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !c.is_ascii_digit() && c != '.' { break; }
+                    number.push(c);
+                    self.chars.next();
+                }
+                number.parse().map_err(|_| DdddocrError::Unrecognized(format!("invalid number: {}", number)))
+            }
+            Some(c) => bail!(Unrecognized, "unexpected token: {}", c),
+            None => bail!(Unrecognized, "unexpected end of expression"),
+        }
+    }
+}
+
+/// Evaluates a normalized expression with the usual precedence rules.
+pub fn eval_expression(expression: &str) -> Result<f64> {
+    let mut parser = Parser { chars: expression.chars().peekable() };
+    let value = parser.expr()?;
+    if let Some(c) = parser.chars.next() {
+        bail!(Unrecognized, "unexpected token: {}", c);
+    }
+    Ok(value)
+}
+
+/// Normalizes and evaluates OCR output such as `3+8=?` or `三乘五等于`.
+pub fn calc_text<T: Into<String>>(text: T) -> Result<CalcResult> {
+    let text = text.into();
+    let expression = normalize_expression(&text)?;
+    let value = eval_expression(&expression)?;
+    Ok(CalcResult { text, expression, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_expression() {
+        assert_eq!(normalize_expression("3+8=?").unwrap(), "3+8");
+        assert_eq!(calc_text("3+8=?").unwrap().value, 11.0);
+    }
+
+    #[test]
+    fn chinese_expression() {
+        assert_eq!(normalize_expression("三乘五等于").unwrap(), "3*5");
+        assert_eq!(calc_text("三乘五等于").unwrap().value, 15.0);
+    }
+
+    #[test]
+    fn chinese_numerals() {
+        assert_eq!(parse_chinese_number("十二"), Some(12));
+        assert_eq!(parse_chinese_number("二十五"), Some(25));
+        assert_eq!(parse_chinese_number("三百零五"), Some(305));
+        assert_eq!(parse_chinese_number("一百二"), Some(120));
+        assert_eq!(parse_chinese_number("三千五"), Some(3500));
+        assert_eq!(parse_chinese_number("二五"), Some(25));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert!(matches!(eval_expression("1/0"), Err(DdddocrError::Unrecognized(_))));
+    }
+
+    #[test]
+    fn overflowing_numeral() {
+        assert_eq!(parse_chinese_number(&"九".repeat(25)), None);
+        assert!(matches!(normalize_expression(&"九".repeat(25)), Err(DdddocrError::Unrecognized(_))));
+    }
+}
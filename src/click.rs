@@ -0,0 +1,109 @@
+use crate::error::{ensure, Result};
+use crate::{BBox, CharacterProbability, Ddddocr};
+
+#[derive(Debug, Clone)]
+pub enum ClickPrompt {
+    /// Characters to click, in order, e.g. `"天地人"`.
+    Text(String),
+    /// Encoded image showing the characters to click, left to right.
+    Image(Vec<u8>),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ClickTarget {
+    /// Prompt character this click answers.
+    pub text: String,
+    /// Click position: the centre of `bbox`.
+    pub x: u32,
+    pub y: u32,
+    pub bbox: BBox,
+    /// What OCR reads in the box on its own, which may differ from `text`.
+    pub ocr_text: String,
+    /// Probability the box shows `text`.
+    pub confidence: f64,
+}
+
+/// Detects every glyph in `image` and classifies each crop.
+fn read_glyphs(ocr: &Ddddocr, det: &Ddddocr, image: &image::DynamicImage) -> Result<Vec<(BBox, CharacterProbability)>> {
+    det.detection_image(image)?
+        .into_iter()
+        .filter(|b| b.x2 > b.x1 && b.y2 > b.y1)
+        .map(|b| {
+            let crop = image.crop_imm(b.x1, b.y1, b.x2 - b.x1, b.y2 - b.y1);
+            Ok((b, ocr.classification_probability_image(&crop, false, &[])?))
+        })
+        .collect()
+}
+
+fn prompt_chars(ocr: &Ddddocr, det: &Ddddocr, prompt: &ClickPrompt) -> Result<Vec<String>> {
+    let text = match prompt {
+        ClickPrompt::Text(v) => v.clone(),
+        ClickPrompt::Image(v) => {
+            let image = image::load_from_memory(v)?;
+            let mut glyphs = read_glyphs(ocr, det, &image)?;
+            if glyphs.is_empty() {
+                // Prompt strips are often too small for the detector; read them as a line.
+                ocr.classification_probability_image(&image, false, &[])?.get_text().to_string()
+            } else {
+                glyphs.sort_by_key(|(b, _)| b.x1);
+                glyphs.iter_mut().map(|(_, p)| p.get_text().to_string()).collect()
+            }
+        }
+    };
+    Ok(text.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_string()).collect())
+}
+
+/// Highest probability `symbol` reaches at any timestep.
+fn symbol_probability(prob: &CharacterProbability, symbol: &str) -> f64 {
+    match prob.charset.iter().position(|c| c == symbol) {
+        Some(i) => prob.probability.iter().map(|row| row[i].max(0.0) as f64).fold(0.0, f64::max),
+        None => 0.0,
+    }
+}
+
+/// Solves a click captcha: finds the box for every prompt character and
+/// returns the clicks in prompt order.
+///
+/// Boxes are assigned greedily by descending probability, so each box is
+/// used at most once even if OCR misreads some of them.
+pub fn click_match<I>(ocr: &Ddddocr, det: &Ddddocr, image: I, prompt: ClickPrompt) -> Result<Vec<ClickTarget>>
+where I: AsRef<[u8]> {
+    let image = image::load_from_memory(image.as_ref())?;
+    let chars = prompt_chars(ocr, det, &prompt)?;
+    ensure!(!chars.is_empty(), InvalidArgument, "empty prompt");
+    let mut glyphs = read_glyphs(ocr, det, &image)?;
+    ensure!(
+        glyphs.len() >= chars.len(), Unrecognized,
+        "found {} glyphs but the prompt has {} characters", glyphs.len(), chars.len()
+    );
+
+    let mut pairs = Vec::with_capacity(chars.len() * glyphs.len());
+    for (i, c) in chars.iter().enumerate() {
+        for (j, (_, prob)) in glyphs.iter().enumerate() {
+            pairs.push((symbol_probability(prob, c), i, j));
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut assigned: Vec<Option<(usize, f64)>> = vec![None; chars.len()];
+    let mut used = vec![false; glyphs.len()];
+    for (score, i, j) in pairs {
+        if assigned[i].is_none() && !used[j] {
+            assigned[i] = Some((j, score));
+            used[j] = true;
+        }
+    }
+
+    Ok(chars.into_iter().zip(assigned).map(|(text, a)| {
+        let (j, confidence) = a.expect("every prompt character gets a box");
+        let (bbox, prob) = &mut glyphs[j];
+        ClickTarget {
+            text,
+            x: (bbox.x1 + bbox.x2) / 2,
+            y: (bbox.y1 + bbox.y2) / 2,
+            bbox: *bbox,
+            ocr_text: prob.get_text().to_string(),
+            confidence,
+        }
+    }).collect())
+}
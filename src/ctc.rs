@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use crate::pattern::{PatternState, TextPattern};
+
+pub const DEFAULT_BEAM_WIDTH: usize = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CtcDecoder {
+    #[default]
+    Greedy,
+    BeamSearch { beam_width: usize },
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CtcCandidate {
+    pub text: String,
+    /// Probability of the decoded string (best path for greedy, summed over
+    /// all alignments for beam search).
+    pub score: f64,
+    /// Confidence of every emitted symbol, aligned with `text`.
+    pub char_confidence: Vec<f64>,
+}
+
+/// The blank is the empty symbol; ddddocr charsets put it first, `calc_ranges` last.
+fn blank_index(charset: &[String]) -> Option<usize> {
+    charset.iter().position(|c| c.is_empty())
+}
+
+fn log_add(a: f64, b: f64) -> f64 {
+    if a == f64::NEG_INFINITY { return b; }
+    if b == f64::NEG_INFINITY { return a; }
+    let m = a.max(b);
+    m + ((a - m).exp() + (b - m).exp()).ln()
+}
+
+fn ln(p: f32) -> f64 {
+    if p > 0.0 { (p as f64).ln() } else { f64::NEG_INFINITY }
+}
+
+pub fn decode(probability: &[Vec<f32>], charset: &[String], decoder: CtcDecoder, top_k: usize) -> Vec<CtcCandidate> {
+    match decoder {
+        CtcDecoder::Greedy => vec![greedy_decode(probability, charset)],
+        CtcDecoder::BeamSearch { beam_width } => beam_search_decode(probability, charset, beam_width, top_k, None),
+    }
+}
+
+/// Best-path decoding: argmax per timestep, merge repeats, then drop blanks.
+pub fn greedy_decode(probability: &[Vec<f32>], charset: &[String]) -> CtcCandidate {
+    let blank = blank_index(charset);
+    let mut text = String::new();
+    let mut char_confidence: Vec<f64> = Vec::new();
+    let mut log_score = 0.0;
+    let mut last = None;
+
+    for row in probability {
+        let (n, p) = row.iter().copied().enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, 0.0));
+        log_score += ln(p);
+        if last == Some(n) {
+            if Some(n) != blank {
+                if let Some(c) = char_confidence.last_mut() { *c = c.max(p as f64); }
+            }
+            continue;
+        }
+        last = Some(n);
+        if Some(n) == blank || charset[n].is_empty() { continue; }
+        text += &charset[n];
+        char_confidence.push(p.max(0.0) as f64);
+    }
+
+    CtcCandidate { text, score: log_score.exp(), char_confidence }
+}
+
+#[derive(Debug, Clone)]
+struct Beam {
+    /// Log probability of all alignments of the prefix ending in a blank.
+    blank: f64,
+    /// Log probability of all alignments of the prefix ending in its last symbol.
+    non_blank: f64,
+    char_confidence: Vec<f64>,
+    pattern: Option<PatternState>,
+}
+
+impl Beam {
+    fn total(&self) -> f64 { log_add(self.blank, self.non_blank) }
+
+    fn extend(&self) -> Self {
+        Self {
+            blank: f64::NEG_INFINITY,
+            non_blank: f64::NEG_INFINITY,
+            char_confidence: self.char_confidence.clone(),
+            pattern: self.pattern,
+        }
+    }
+
+    fn extend_with(&self, p: f32, pattern: Option<PatternState>) -> Self {
+        let mut beam = self.extend();
+        beam.char_confidence.push(p as f64);
+        beam.pattern = pattern;
+        beam
+    }
+}
+
+/// CTC prefix beam search, returning up to `top_k` strings ordered by probability.
+/// Only the `beam_width` most likely symbols of each timestep are expanded.
+///
+/// With a `pattern`, prefixes that can no longer match are pruned as they are
+/// built and only strings satisfying it are returned, possibly none.
+pub fn beam_search_decode(
+    probability: &[Vec<f32>],
+    charset: &[String],
+    beam_width: usize,
+    top_k: usize,
+    pattern: Option<&TextPattern>,
+) -> Vec<CtcCandidate> {
+    let blank = blank_index(charset);
+    let beam_width = beam_width.max(1);
+    let mut beams: Vec<(Vec<usize>, Beam)> = vec![(
+        Vec::new(),
+        Beam {
+            blank: 0.0,
+            non_blank: f64::NEG_INFINITY,
+            char_confidence: Vec::new(),
+            pattern: pattern.map(TextPattern::start),
+        },
+    )];
+
+    for row in probability {
+        let mut symbols: Vec<usize> = (0..row.len())
+            .filter(|&i| Some(i) != blank && row[i] > 0.0 && !charset[i].is_empty())
+            .collect();
+        symbols.sort_by(|&a, &b| row[b].total_cmp(&row[a]));
+        // Unconstrained, the same top symbols are expanded for every beam.
+        // Constrained, each beam takes the most likely symbols it can still accept.
+        if pattern.is_none() { symbols.truncate(beam_width); }
+
+        let mut next: HashMap<Vec<usize>, Beam> = HashMap::new();
+        for (prefix, beam) in &beams {
+            let total = beam.total();
+
+            if let Some(b) = blank {
+                let entry = next.entry(prefix.clone()).or_insert_with(|| beam.extend());
+                entry.blank = log_add(entry.blank, total + ln(row[b]));
+            }
+
+            let mut expanded = 0;
+            for &s in &symbols {
+                if expanded == beam_width { break; }
+                let p = ln(row[s]);
+
+                if prefix.last() == Some(&s) {
+                    // A repeated symbol only starts a new character after a blank.
+                    let entry = next.entry(prefix.clone()).or_insert_with(|| beam.extend());
+                    entry.non_blank = log_add(entry.non_blank, beam.non_blank + p);
+                    if let Some(c) = entry.char_confidence.last_mut() { *c = c.max(row[s] as f64); }
+                }
+
+                let state = match (pattern, beam.pattern) {
+                    (Some(pattern), Some(state)) => match pattern.advance(state, &charset[s]) {
+                        Some(v) => Some(v),
+                        None => continue,
+                    },
+                    _ => None,
+                };
+                expanded += 1;
+
+                let mut extended = prefix.clone();
+                extended.push(s);
+                let from = if prefix.last() == Some(&s) { beam.blank } else { total };
+                let entry = next.entry(extended).or_insert_with(|| beam.extend_with(row[s], state));
+                entry.non_blank = log_add(entry.non_blank, from + p);
+            }
+        }
+
+        beams = next.into_iter().filter(|(_, b)| b.total() > f64::NEG_INFINITY).collect();
+        beams.sort_by(|(_, a), (_, b)| b.total().total_cmp(&a.total()));
+        beams.truncate(beam_width);
+        if beams.is_empty() { break; }
+    }
+
+    beams.into_iter()
+        .filter(|(_, beam)| match (pattern, beam.pattern) {
+            (Some(pattern), Some(state)) => pattern.accepts(state),
+            _ => true,
+        })
+        .take(top_k.max(1))
+        .map(|(prefix, beam)| CtcCandidate {
+            text: prefix.iter().map(|&i| charset[i].as_str()).collect(),
+            score: beam.total().exp(),
+            char_confidence: beam.char_confidence,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charset() -> Vec<String> {
+        ["", "a", "b"].iter().map(|v| v.to_string()).collect()
+    }
+
+    /// One row per timestep over `["", "a", "b"]`, mostly on `symbol`.
+    fn rows(symbols: &[usize]) -> Vec<Vec<f32>> {
+        symbols.iter().map(|&s| {
+            let mut row = vec![0.05; 3];
+            row[s] = 0.9;
+            row
+        }).collect()
+    }
+
+    #[test]
+    fn greedy_collapses_repeats_and_blanks() {
+        let res = greedy_decode(&rows(&[1, 1, 0, 2, 2, 0]), &charset());
+        assert_eq!(res.text, "ab");
+        assert_eq!(res.char_confidence.len(), 2);
+    }
+
+    #[test]
+    fn blank_separates_double_letters() {
+        assert_eq!(greedy_decode(&rows(&[1, 0, 1]), &charset()).text, "aa");
+        assert_eq!(greedy_decode(&rows(&[1, 1]), &charset()).text, "a");
+        assert_eq!(beam_search_decode(&rows(&[1, 0, 1]), &charset(), 5, 1, None)[0].text, "aa");
+        assert_eq!(beam_search_decode(&rows(&[1, 1]), &charset(), 5, 1, None)[0].text, "a");
+    }
+
+    #[test]
+    fn beam_agrees_with_greedy_on_peaked_input() {
+        let probability = rows(&[2, 0, 1, 1, 0, 2]);
+        let greedy = greedy_decode(&probability, &charset());
+        let beam = beam_search_decode(&probability, &charset(), 5, 1, None);
+        assert_eq!(beam[0].text, greedy.text);
+        assert!(beam[0].score >= greedy.score);
+    }
+
+    #[test]
+    fn beam_sums_alignments_and_orders_candidates() {
+        // The best single path is two blanks, but "a" wins once its three
+        // alignments (a a, a _, _ a) are summed: 0.64 against 0.36.
+        let probability = vec![vec![0.6, 0.4, 0.0]; 2];
+        assert_eq!(greedy_decode(&probability, &charset()).text, "");
+        let beam = beam_search_decode(&probability, &charset(), 5, 3, None);
+        let texts: Vec<_> = beam.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["a", ""]);
+        assert!((beam[0].score - 0.64).abs() < 1e-6);
+        assert!(beam.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{ParseCharsetRangeError, ParseColorError};
+
+/// Everything the library can fail with.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DdddocrError {
+    /// The bytes are not an image in a format we can read.
+    #[error("image decode failed: {0}")]
+    ImageDecode(#[from] image::ImageError),
+    /// Inputs whose sizes do not fit together, e.g. a slider piece larger
+    /// than its background.
+    #[error("size mismatch: {0}")]
+    SizeMismatch(String),
+    /// The charset JSON is malformed or inconsistent.
+    #[error("invalid charset: {0}")]
+    Charset(String),
+    #[error(transparent)]
+    CharsetRange(#[from] ParseCharsetRangeError),
+    #[error(transparent)]
+    Color(#[from] ParseColorError),
+    /// A `TextPattern` that does not compile.
+    #[error("invalid pattern: {0}")]
+    Pattern(String),
+    /// Any other argument the call cannot work with.
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// The model is the wrong kind for the call, or its output does not
+    /// have the expected shape.
+    #[error("model mismatch: {0}")]
+    Model(String),
+    /// ONNX Runtime failed to load or run the model.
+    #[error("inference failed: {0}")]
+    Inference(#[from] ort::Error),
+    /// The input is valid but holds nothing to read: an expression that does
+    /// not parse, an image without edges, fewer glyphs than asked for.
+    #[error("{0}")]
+    Unrecognized(String),
+}
+
+impl DdddocrError {
+    /// Stable snake_case name of the variant, for logs and API clients.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ImageDecode(_) => "image_decode_failed",
+            Self::SizeMismatch(_) => "size_mismatch",
+            Self::Charset(_) => "invalid_charset",
+            Self::CharsetRange(_) => "invalid_charset_range",
+            Self::Color(_) => "invalid_color",
+            Self::Pattern(_) => "invalid_pattern",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::Model(_) => "model_mismatch",
+            Self::Inference(_) => "inference_failed",
+            Self::Unrecognized(_) => "unrecognized",
+        }
+    }
+}
+
+/// Tensors are shaped from our own buffers and the model's output, so a
+/// shape error means the model is not what we expect.
+impl From<ndarray::ShapeError> for DdddocrError {
+    fn from(e: ndarray::ShapeError) -> Self { Self::Model(e.to_string()) }
+}
+
+pub type Result<T, E = DdddocrError> = std::result::Result<T, E>;
+
+/// Returns early with the given variant unless the condition holds, like
+/// `anyhow::ensure!`.
+macro_rules! ensure {
+    ($cond:expr, $variant:ident, $($arg:tt)+) => {
+        let ok: bool = $cond;
+        if !ok {
+            return Err($crate::DdddocrError::$variant(format!($($arg)+)));
+        }
+    };
+}
+
+/// Returns early with the given variant, like `anyhow::bail!`.
+macro_rules! bail {
+    ($variant:ident, $($arg:tt)+) => {
+        return Err($crate::DdddocrError::$variant(format!($($arg)+)))
+    };
+}
+
+pub(crate) use {bail, ensure};
@@ -1,20 +1,60 @@
 use image::{GenericImageView, GenericImage};
 use ort::session::Session;
+use self::pool::SessionPool;
+use self::error::{bail, ensure, Result};
 
 // Re-export internal structs if needed by main
-pub use self::color_filter::{Color, ColorFilter, IntoHsvRange};
-pub use self::charset::{Charset, CharsetRange};
+pub use self::error::DdddocrError;
+pub use self::color_filter::{Color, ColorFilter, HsvRange, IntoHsvRange, ParseColorError};
+pub use self::charset::{Charset, CharsetRange, ColorMode, ParseCharsetRangeError, ResizeFilter};
+use self::charset::Normalization;
+pub use self::calc::{calc_text, eval_expression, normalize_expression, CalcResult, CALC_CHARSET};
+pub use self::click::{click_match, ClickPrompt, ClickTarget};
+pub use self::ctc::{CtcCandidate, CtcDecoder, DEFAULT_BEAM_WIDTH};
+pub use self::pattern::TextPattern;
+pub use self::pool::SessionOptions;
+pub use self::rotate::{rotate_match, RotateResult};
+pub use self::trajectory::{generate_track, TrackOptions, TrackPoint, TrackPreset, MAX_TRACK_DURATION_MS};
+
+mod calc;
+mod click;
+mod ctc;
+mod error;
+mod pattern;
+mod pool;
+mod rotate;
+mod trajectory;
 
 mod color_filter {
+    pub type HsvRange = ((u8, u8, u8), (u8, u8, u8));
+
     #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub enum Color {
         Red, Blue, Green, Yellow, Orange, Purple, Cyan, Black, White, Gray,
     }
 
-    impl<T: AsRef<str>> From<T> for Color {
-        fn from(value: T) -> Self {
-            match value.as_ref().to_ascii_lowercase().as_str() {
+    impl Color {
+        /// Every colour name `from_str` accepts, in declaration order.
+        pub const NAMES: [&'static str; 10] = ["red", "blue", "green", "yellow", "orange", "purple", "cyan", "black", "white", "gray"];
+    }
+
+    /// A colour name that is not one of `Color::NAMES`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ParseColorError(pub String);
+
+    impl std::fmt::Display for ParseColorError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "unknown color: {} (expected one of {})", self.0, Color::NAMES.join(", "))
+        }
+    }
+
+    impl std::error::Error for ParseColorError {}
+
+    impl std::str::FromStr for Color {
+        type Err = ParseColorError;
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            Ok(match value.to_ascii_lowercase().as_str() {
                 "red" => Color::Red,
                 "blue" => Color::Blue,
                 "green" => Color::Green,
@@ -25,17 +65,27 @@ mod color_filter {
                 "black" => Color::Black,
                 "white" => Color::White,
                 "gray" => Color::Gray,
-                _ => panic!("unknown color: {}", value.as_ref()),
-            }
+                _ => return Err(ParseColorError(value.to_string())),
+            })
         }
     }
 
+    impl TryFrom<&str> for Color {
+        type Error = ParseColorError;
+        fn try_from(value: &str) -> Result<Self, Self::Error> { value.parse() }
+    }
+
+    impl TryFrom<String> for Color {
+        type Error = ParseColorError;
+        fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+    }
+
     pub trait IntoHsvRange {
-        fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))>;
+        fn into_hsv_ranges(self) -> Vec<HsvRange>;
     }
 
     impl IntoHsvRange for Color {
-        fn into_hsv_ranges(self) -> Vec<((u8, u8, u8), (u8, u8, u8))> {
+        fn into_hsv_ranges(self) -> Vec<HsvRange> {
             match self {
                 Color::Red => vec![((0, 50, 50), (10, 255, 255)), ((170, 50, 50), (180, 255, 255))],
                 Color::Blue => vec![((100, 50, 50), (140, 255, 255))],
@@ -52,15 +102,36 @@ mod color_filter {
     }
 
     #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-    #[serde(untagged)]
+    #[serde(untagged, try_from = "RawColorFilter")]
     pub enum ColorFilter {
-        HSVRanges(Vec<((u8, u8, u8), (u8, u8, u8))>),
+        HSVRanges(Vec<HsvRange>),
         ColorRanges(Vec<Color>),
         Color(Color),
     }
 
+    /// Colour names are parsed after the shape is known, so that a typo is
+    /// reported as such instead of as "did not match any variant".
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum RawColorFilter {
+        HSVRanges(Vec<HsvRange>),
+        ColorRanges(Vec<String>),
+        Color(String),
+    }
+
+    impl TryFrom<RawColorFilter> for ColorFilter {
+        type Error = ParseColorError;
+        fn try_from(raw: RawColorFilter) -> Result<Self, Self::Error> {
+            Ok(match raw {
+                RawColorFilter::HSVRanges(v) => ColorFilter::HSVRanges(v),
+                RawColorFilter::ColorRanges(v) => ColorFilter::ColorRanges(v.iter().map(|c| c.parse()).collect::<Result<_, _>>()?),
+                RawColorFilter::Color(v) => ColorFilter::Color(v.parse()?),
+            })
+        }
+    }
+
     impl ColorFilter {
-        pub fn filter<I>(&self, image: I) -> anyhow::Result<image::DynamicImage>
+        pub fn filter<I>(&self, image: I) -> crate::error::Result<image::DynamicImage>
         where
             I: AsRef<[u8]>,
         {
@@ -107,8 +178,8 @@ mod color_filter {
 
             let ranges = match self {
                 ColorFilter::HSVRanges(v) => v.clone(),
-                ColorFilter::ColorRanges(v) => v.iter().flat_map(|v| v.clone().into_hsv_ranges()).collect(),
-                ColorFilter::Color(v) => v.clone().into_hsv_ranges(),
+                ColorFilter::ColorRanges(v) => v.iter().flat_map(|v| (*v).into_hsv_ranges()).collect(),
+                ColorFilter::Color(v) => (*v).into_hsv_ranges(),
             };
 
             for (lower, upper) in ranges {
@@ -139,23 +210,201 @@ mod color_filter {
         }
     }
     
-    impl From<&str> for ColorFilter { fn from(v: &str) -> Self { Color::from(v).into_hsv_ranges().into() } }
-    impl From<Vec<((u8, u8, u8), (u8, u8, u8))>> for ColorFilter { fn from(v: Vec<((u8, u8, u8), (u8, u8, u8))>) -> Self { ColorFilter::HSVRanges(v) } }
+    impl TryFrom<&str> for ColorFilter {
+        type Error = ParseColorError;
+        fn try_from(v: &str) -> Result<Self, Self::Error> { Ok(v.parse::<Color>()?.into_hsv_ranges().into()) }
+    }
+    impl From<Vec<HsvRange>> for ColorFilter { fn from(v: Vec<HsvRange>) -> Self { ColorFilter::HSVRanges(v) } }
     // ... omitting excessive implementation boilerplate for brevity where feasible
 }
 
 mod charset {
+    /// Character set and input geometry of an OCR model, i.e. the JSON file
+    /// next to the ONNX model.
+    ///
+    /// Besides ddddocr's own format this reads the key spellings used by
+    /// dddd_trainer configs and common forks (`resize`, `charsets`,
+    /// `ImageWidth`/`ImageHeight`, ...); see `RawCharset`.
     #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    #[serde(try_from = "RawCharset")]
     pub struct Charset {
         pub word: bool,
         pub image: [i64; 2],
         pub channel: i64,
         pub charset: Vec<String>,
+        /// Per-channel mean subtracted after scaling pixels to `[0, 1]`, one
+        /// value or one per channel. With `std`, overrides the guess made from
+        /// the model hash (`is_diy`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub mean: Option<Vec<f32>>,
+        /// Per-channel standard deviation, one value or one per channel.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub std: Option<Vec<f32>>,
+        /// Defaults to `lanczos3`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub resize_filter: Option<ResizeFilter>,
+        /// Defaults to `gray` for one channel and `rgb` for three.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub color_mode: Option<ColorMode>,
+        /// Range the model's output is restricted to by default, in the
+        /// `CharsetRange` string form (a preset ID 0-7 or the characters).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub charset_range: Option<String>,
+    }
+
+    /// Either a list of symbols or all of them in one string.
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum RawSymbols {
+        List(Vec<String>),
+        Joined(String),
+    }
+
+    /// Every spelling of the charset JSON we know of. Unknown keys are ignored.
+    #[derive(serde::Deserialize)]
+    struct RawCharset {
+        #[serde(default, alias = "Word")]
+        word: Option<bool>,
+        #[serde(default, alias = "resize", alias = "image_size", alias = "ImageSize")]
+        image: Option<[i64; 2]>,
+        #[serde(default, alias = "ImageWidth", alias = "image_width")]
+        width: Option<i64>,
+        #[serde(default, alias = "ImageHeight", alias = "image_height")]
+        height: Option<i64>,
+        #[serde(default, alias = "channels", alias = "image_channel", alias = "ImageChannel")]
+        channel: Option<i64>,
+        #[serde(default, alias = "charsets", alias = "Charset", alias = "character")]
+        charset: Option<RawSymbols>,
+        #[serde(default)]
+        mean: Option<Vec<f32>>,
+        #[serde(default)]
+        std: Option<Vec<f32>>,
+        #[serde(default)]
+        resize_filter: Option<ResizeFilter>,
+        #[serde(default)]
+        color_mode: Option<ColorMode>,
+        #[serde(default)]
+        charset_range: Option<String>,
+    }
+
+    impl TryFrom<RawCharset> for Charset {
+        type Error = String;
+        fn try_from(raw: RawCharset) -> Result<Self, Self::Error> {
+            let image = match (raw.image, raw.width, raw.height) {
+                (Some(image), _, _) => image,
+                (None, Some(width), Some(height)) => [width, height],
+                (None, None, None) => return Err("charset JSON is missing `image` (or `resize`, `image_size`, `ImageWidth`/`ImageHeight`)".into()),
+                (None, _, None) => return Err("charset JSON has a width but no `ImageHeight`".into()),
+                (None, None, _) => return Err("charset JSON has a height but no `ImageWidth`".into()),
+            };
+            let channel = raw.channel
+                .ok_or("charset JSON is missing `channel` (or `channels`, `image_channel`, `ImageChannel`)")?;
+            if channel != 1 && channel != 3 {
+                return Err(format!("charset JSON `channel` must be 1 or 3, got {}", channel));
+            }
+            if image[1] <= 0 || image[0] == 0 || image[0] < -1 {
+                return Err(format!("charset JSON `image` must be [width or -1, height], got {:?}", image));
+            }
+            let charset = match raw.charset.ok_or("charset JSON is missing `charset` (or `charsets`, `character`)")? {
+                RawSymbols::List(v) => v,
+                // A joined string cannot hold the CTC blank, which every
+                // model expects at index 0.
+                RawSymbols::Joined(v) => std::iter::once(String::new()).chain(v.chars().map(|c| c.to_string())).collect(),
+            };
+            if charset.is_empty() {
+                return Err("charset JSON `charset` is empty".into());
+            }
+            Ok(Self {
+                word: raw.word.unwrap_or(false),
+                image,
+                channel,
+                charset,
+                mean: raw.mean,
+                std: raw.std,
+                resize_filter: raw.resize_filter,
+                color_mode: raw.color_mode,
+                charset_range: raw.charset_range,
+            })
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ResizeFilter {
+        Nearest,
+        Triangle,
+        CatmullRom,
+        Gaussian,
+        Lanczos3,
+    }
+
+    impl From<ResizeFilter> for image::imageops::FilterType {
+        fn from(value: ResizeFilter) -> Self {
+            match value {
+                ResizeFilter::Nearest => Self::Nearest,
+                ResizeFilter::Triangle => Self::Triangle,
+                ResizeFilter::CatmullRom => Self::CatmullRom,
+                ResizeFilter::Gaussian => Self::Gaussian,
+                ResizeFilter::Lanczos3 => Self::Lanczos3,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ColorMode {
+        Gray,
+        Rgb,
+        /// Channels in OpenCV order.
+        Bgr,
+    }
+
+    /// Fully resolved preprocessing for an OCR model.
+    #[derive(Debug, Clone)]
+    pub(crate) struct Normalization {
+        pub(crate) mean: Vec<f32>,
+        pub(crate) std: Vec<f32>,
+        pub(crate) filter: image::imageops::FilterType,
+        pub(crate) color_mode: ColorMode,
+    }
+
+    impl Charset {
+        /// Preprocessing declared by the charset, with whatever is missing
+        /// taken from the hash heuristic (`diy`).
+        pub(crate) fn normalization(&self, diy: bool) -> crate::error::Result<Normalization> {
+            let color_mode = match (self.color_mode, self.channel) {
+                (Some(ColorMode::Gray), 1) | (None, 1) => ColorMode::Gray,
+                (Some(mode @ (ColorMode::Rgb | ColorMode::Bgr)), 3) => mode,
+                (None, 3) => ColorMode::Rgb,
+                (mode, channel) => crate::error::bail!(Charset, "color_mode {:?} does not fit channel {}", mode, channel),
+            };
+            let channel = self.channel as usize;
+            let (mean, std) = match (&self.mean, &self.std, diy, channel) {
+                (Some(mean), Some(std), _, _) => (mean.clone(), std.clone()),
+                (None, None, true, 1) => (vec![0.456], vec![0.224]),
+                (None, None, true, _) => (vec![0.485, 0.456, 0.406], vec![0.229, 0.224, 0.225]),
+                (None, None, false, _) => (vec![0.5], vec![0.5]),
+                _ => crate::error::bail!(Charset, "mean and std must be given together"),
+            };
+            for (name, v) in [("mean", &mean), ("std", &std)] {
+                crate::error::ensure!(v.len() == 1 || v.len() == channel, Charset, "{} must have one value or one per channel ({}), got {}", name, channel, v.len());
+            }
+            crate::error::ensure!(std.iter().all(|v| *v != 0.0), Charset, "std must not contain 0");
+            let expand = |v: Vec<f32>| if v.len() == 1 { vec![v[0]; channel] } else { v };
+            Ok(Normalization {
+                mean: expand(mean),
+                std: expand(std),
+                filter: self.resize_filter.unwrap_or(ResizeFilter::Lanczos3).into(),
+                color_mode,
+            })
+        }
     }
     
     impl std::str::FromStr for Charset {
-        type Err = serde_json::Error;
-        fn from_str(s: &str) -> Result<Self, Self::Err> { serde_json::from_str(s) }
+        type Err = crate::DdddocrError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            serde_json::from_str(s).map_err(|e| crate::DdddocrError::Charset(e.to_string()))
+        }
     }
 
     #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -164,16 +413,50 @@ mod charset {
         DefaultCharsetLowercaseUppercaseDigit, Other(String), Charset(Vec<String>),
     }
 
-    impl From<i32> for CharsetRange {
-        fn from(value: i32) -> Self {
-             match value {
+    /// A charset range that is neither a preset ID (0-7) nor a non-empty
+    /// list of characters.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ParseCharsetRangeError {
+        Preset(i32),
+        Empty,
+    }
+
+    impl std::fmt::Display for ParseCharsetRangeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Preset(v) => write!(f, "invalid charset range preset: {} (expected 0-7)", v),
+                Self::Empty => write!(f, "empty charset range (expected a preset ID 0-7 or the allowed characters)"),
+            }
+        }
+    }
+
+    impl std::error::Error for ParseCharsetRangeError {}
+
+    impl TryFrom<i32> for CharsetRange {
+        type Error = ParseCharsetRangeError;
+        fn try_from(value: i32) -> Result<Self, Self::Error> {
+            Ok(match value {
                 0 => Self::Digit, 1 => Self::Lowercase, 2 => Self::Uppercase, 3 => Self::LowercaseUppercase,
                 4 => Self::LowercaseDigit, 5 => Self::UppercaseDigit, 6 => Self::LowercaseUppercaseDigit,
-                7 => Self::DefaultCharsetLowercaseUppercaseDigit, _ => panic!("invalid range"),
+                7 => Self::DefaultCharsetLowercaseUppercaseDigit, _ => return Err(ParseCharsetRangeError::Preset(value)),
+            })
+        }
+    }
+
+    /// A single digit `0`-`7` is a preset ID; anything else lists the allowed
+    /// characters, e.g. `"0123456789+-"`. Use `From<&str>` to always take the
+    /// characters literally.
+    impl std::str::FromStr for CharsetRange {
+        type Err = ParseCharsetRangeError;
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value {
+                "" => Err(ParseCharsetRangeError::Empty),
+                "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" => Self::try_from(value.as_bytes()[0] as i32 - '0' as i32),
+                v => Ok(Self::Other(v.to_string())),
             }
         }
     }
-    
+
     impl From<&str> for CharsetRange { fn from(v: &str) -> Self { Self::Other(v.to_string()) } }
     impl From<String> for CharsetRange { fn from(v: String) -> Self { Self::Other(v) } }
 }
@@ -183,10 +466,28 @@ pub struct BBox {
     pub x1: u32, pub y1: u32, pub x2: u32, pub y2: u32,
 }
 
+/// A detector hit with its confidence.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct DetectionBBox {
+    pub x1: u32, pub y1: u32, pub x2: u32, pub y2: u32,
+    /// Objectness times the probability of `class_id`.
+    pub score: f32,
+    /// Index of the most likely class; always 0 for the bundled single-class model.
+    pub class_id: u32,
+}
+
+impl DetectionBBox {
+    pub fn bbox(&self) -> BBox {
+        BBox { x1: self.x1, y1: self.y1, x2: self.x2, y2: self.y2 }
+    }
+}
+
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct SlideBBox {
     pub target_x: u32, pub target_y: u32,
     pub x1: u32, pub y1: u32, pub x2: u32, pub y2: u32,
+    /// Normalized cross-correlation of the edge maps, in `[0, 1]`.
+    pub score: f32,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -195,65 +496,133 @@ pub struct CharacterProbability {
     pub charset: Vec<String>,
     pub probability: Vec<Vec<f32>>,
     pub confidence: Option<f64>,
+    pub char_confidence: Option<Vec<f64>>,
 }
 
 impl CharacterProbability {
+    fn new(charset: Vec<String>, probability: Vec<Vec<f32>>) -> Self {
+        let mut this = Self { text: None, charset, probability, confidence: None, char_confidence: None };
+        this.decode_best();
+        this
+    }
+
+    fn decode_best(&mut self) {
+        let best = ctc::greedy_decode(&self.probability, &self.charset);
+        self.text = Some(best.text);
+        self.confidence = Some(best.score);
+        self.char_confidence = Some(best.char_confidence);
+    }
+
     pub fn get_text(&mut self) -> &str {
-        self.text.get_or_insert_with(|| {
-            let mut s = String::new();
-            for i in &self.probability {
-                let (n, _) = i.iter().enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b)).unwrap();
-                s += &self.charset[n];
-            }
-            s
-        })
+        if self.text.is_none() { self.decode_best(); }
+        self.text.as_deref().unwrap()
+    }
+
+    /// Probability of the greedy path, i.e. the product of the winning
+    /// probability at every timestep (blanks included).
+    pub fn get_confidence(&mut self) -> f64 {
+        if self.confidence.is_none() { self.decode_best(); }
+        self.confidence.unwrap()
+    }
+
+    /// Confidence of every emitted symbol, aligned with the decoded text.
+    pub fn get_char_confidence(&mut self) -> &[f64] {
+        if self.char_confidence.is_none() { self.decode_best(); }
+        self.char_confidence.as_deref().unwrap()
+    }
+
+    /// CTC-decodes the probabilities, returning up to `top_k` candidates best first.
+    pub fn decode(&self, decoder: CtcDecoder, top_k: usize) -> Vec<CtcCandidate> {
+        ctc::decode(&self.probability, &self.charset, decoder, top_k)
     }
+
+    /// Like `decode` with beam search, but only returns strings matching `pattern`.
+    pub fn decode_with_pattern(&self, pattern: &TextPattern, beam_width: usize, top_k: usize) -> Vec<CtcCandidate> {
+        ctc::beam_search_decode(&self.probability, &self.charset, beam_width, top_k, Some(pattern))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DetectionOptions {
+    /// Boxes scoring below this (objectness times class probability) are dropped.
+    pub score_threshold: f32,
+    /// Overlapping boxes with an IoU above this are suppressed in favour of the higher score.
+    pub nms_threshold: f32,
 }
 
-lazy_static::lazy_static! {
-    static ref _STATIC: (Vec<u32>, Vec<u32>) = {
+impl Default for DetectionOptions {
+    fn default() -> Self { Self { score_threshold: 0.1, nms_threshold: 0.45 } }
+}
+
+/// Input size used when the model does not declare a fixed one.
+const DEFAULT_DET_SIZE: (u32, u32) = (416, 416);
+const STRIDES: [u32; 3] = [8, 16, 32];
+
+/// YOLOX anchor grid for one input size: the cell offset (x, y) and stride
+/// of every output row.
+#[derive(Debug, Clone)]
+struct DetectionGrid {
+    width: u32,
+    height: u32,
+    grids: Vec<u32>,
+    expanded_strides: Vec<u32>,
+}
+
+impl DetectionGrid {
+    fn new(width: u32, height: u32) -> Self {
         let mut grids = Vec::new();
         let mut expanded_strides = Vec::new();
-        let hsizes = STRIDES.iter().map(|v| MODEL_HEIGHT / v).collect::<Vec<_>>();
-        let wsizes = STRIDES.iter().map(|v| MODEL_WIDTH / v).collect::<Vec<_>>();
-        for (i, v) in STRIDES.iter().enumerate() {
-            let hsize = hsizes[i];
-            let wsize = wsizes[i];
-            let mut grid = vec![0; (hsize * wsize * 2) as usize];
+        for stride in STRIDES {
+            let hsize = height / stride;
+            let wsize = width / stride;
             for i in 0..hsize {
                 for j in 0..wsize {
-                    let index = ((i * wsize + j) * 2) as usize;
-                    grid[index] = j;
-                    grid[index + 1] = i;
+                    grids.extend([j, i]);
                 }
             }
-            grids.extend(grid);
-            expanded_strides.extend(vec![*v; (hsize * wsize) as usize]);
+            expanded_strides.extend(vec![stride; (hsize * wsize) as usize]);
         }
-        (grids, expanded_strides)
-    };
-    static ref GRIDS: Vec<u32> = unsafe { std::mem::transmute_copy(&_STATIC.0) };
-    static ref EXPANDED_STRIDES: Vec<u32> =  unsafe { std::mem::transmute_copy(&_STATIC.1) };
+        Self { width, height, grids, expanded_strides }
+    }
 }
 
-const NMS_THR: f32 = 0.45;
-const SCORE_THR: f32 = 0.1;
-const MODEL_WIDTH: u32 = 416;
-const MODEL_HEIGHT: u32 = 416;
-const STRIDES: [u32; 3] = [8, 16, 32];
+/// Declared shape of the model's first input, with `-1` for dynamic dimensions.
+fn model_input_shape(session: &Session) -> Vec<i64> {
+    session.inputs.first()
+        .and_then(|v| v.input_type.tensor_shape())
+        .map(|v| v.to_vec())
+        .unwrap_or_default()
+}
+
+/// Width and height from an NCHW image input, if the model fixes them.
+fn model_input_size(shape: &[i64]) -> Option<(u32, u32)> {
+    match shape[..] {
+        [_, _, h, w] if h > 0 && w > 0 => Some((w as u32, h as u32)),
+        _ => None,
+    }
+}
 
 pub struct Ddddocr<'a> {
     diy: bool,
-    session: std::sync::Mutex<Session>,
+    sha256: String,
+    sessions: SessionPool,
+    input_shape: Vec<i64>,
     charset: Option<std::borrow::Cow<'a, Charset>>,
+    normalization: Option<Normalization>,
     charset_range: Vec<String>,
+    detection_grid: Option<DetectionGrid>,
+    detection_options: DetectionOptions,
 }
 
 unsafe impl<'a> Send for Ddddocr<'a> {}
 unsafe impl<'a> Sync for Ddddocr<'a> {}
 
 pub fn is_diy(model: &[u8]) -> bool {
-    let sha = sha256::digest(model);
+    is_diy_sha256(&sha256::digest(model))
+}
+
+/// Anything but the two bundled ddddocr OCR models counts as DIY.
+fn is_diy_sha256(sha: &str) -> bool {
     sha != "33b5cd351ee94e73a6bf8fa18c415ed8b819b3ffd342e267c30d8ad8334e34e8"
         && sha != "b8f2ad9cbc1f2e3922a6cb9459e30824e7e2467f3fb4fd61420640e34ea0bf68"
 }
@@ -272,26 +641,120 @@ fn png_rgba_black_preprocess(image: &image::DynamicImage) -> image::DynamicImage
 }
 
 impl<'a> Ddddocr<'a> {
-    pub fn new<MODEL>(model: MODEL, charset: Charset) -> anyhow::Result<Self>
+    pub fn new<MODEL>(model: MODEL, charset: Charset) -> Result<Self>
     where MODEL: AsRef<[u8]> {
-        Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: std::sync::Mutex::new(Session::builder()?.commit_from_memory(model.as_ref())?),
+        Self::new_with_options(model, charset, SessionOptions::default())
+    }
+
+    pub fn new_with_options<MODEL>(model: MODEL, charset: Charset, options: SessionOptions) -> Result<Self>
+    where MODEL: AsRef<[u8]> {
+        let sha256 = sha256::digest(model.as_ref());
+        let diy = is_diy_sha256(&sha256);
+        let normalization = charset.normalization(diy)?;
+        let range = charset.charset_range.as_deref().map(str::parse::<CharsetRange>).transpose()?;
+        let sessions = SessionPool::new(model.as_ref(), &options)?;
+        let input_shape = model_input_shape(&sessions.get());
+        let mut instance = Self {
+            diy,
+            sha256,
+            sessions,
+            input_shape,
             charset: Some(std::borrow::Cow::Owned(charset)),
+            normalization: Some(normalization),
             charset_range: Vec::new(),
-        })
+            detection_grid: None,
+            detection_options: DetectionOptions::default(),
+        };
+        if let Some(range) = range {
+            instance.set_ranges(range);
+        }
+        Ok(instance)
     }
 
-    pub fn new_det<MODEL>(model: MODEL) -> anyhow::Result<Self> 
+    /// Loads a YOLOX-style detector. The input size comes from the model's
+    /// input shape, or 416x416 when it is dynamic.
+    pub fn new_det<MODEL>(model: MODEL) -> Result<Self> 
     where MODEL: AsRef<[u8]> {
+        Self::new_det_with_options(model, SessionOptions::default())
+    }
+
+    pub fn new_det_with_options<MODEL>(model: MODEL, options: SessionOptions) -> Result<Self> 
+    where MODEL: AsRef<[u8]> {
+        let sha256 = sha256::digest(model.as_ref());
+        let sessions = SessionPool::new(model.as_ref(), &options)?;
+        let input_shape = model_input_shape(&sessions.get());
+        let (width, height) = model_input_size(&input_shape).unwrap_or(DEFAULT_DET_SIZE);
         Ok(Self {
-            diy: is_diy(model.as_ref()),
-            session: std::sync::Mutex::new(Session::builder()?.commit_from_memory(model.as_ref())?),
+            diy: is_diy_sha256(&sha256),
+            sha256,
+            sessions,
+            input_shape,
             charset: None,
+            normalization: None,
             charset_range: Vec::new(),
+            detection_grid: Some(DetectionGrid::new(width, height)),
+            detection_options: DetectionOptions::default(),
         })
     }
 
+    /// Hex SHA-256 of the model file.
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    /// Whether the model hash marks it as custom-trained, which selects
+    /// ImageNet-style normalization unless the charset declares its own.
+    pub fn diy(&self) -> bool {
+        self.diy
+    }
+
+    /// Charset of an OCR model, or `None` for detectors.
+    pub fn charset(&self) -> Option<&Charset> {
+        self.charset.as_deref()
+    }
+
+    /// Declared shape of the model input, with `-1` for dynamic dimensions.
+    pub fn input_shape(&self) -> &[i64] {
+        &self.input_shape
+    }
+
+    /// Runs one inference on a blank image to check that the model loads,
+    /// accepts its input and, for OCR, agrees with the charset.
+    pub fn validate(&self) -> Result<()> {
+        match (&self.charset, &self.detection_grid) {
+            (Some(charset), _) => {
+                let height = if charset.image[1] > 0 { charset.image[1] as u32 } else { 64 };
+                let blank = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(height * 2, height, image::Rgb([255, 255, 255])));
+                self.classification_probability_image(&blank, false, &[])?;
+            }
+            (None, Some(grid)) => {
+                let blank = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(grid.width, grid.height, image::Rgb([255, 255, 255])));
+                self.detection_image(&blank)?;
+            }
+            (None, None) => bail!(Model, "model has neither a charset nor a detection grid"),
+        }
+        Ok(())
+    }
+
+    /// Number of sessions that can run inference concurrently.
+    pub fn pool_size(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Detector input size as `(width, height)`, or `None` for OCR models.
+    pub fn detection_input_size(&self) -> Option<(u32, u32)> {
+        self.detection_grid.as_ref().map(|g| (g.width, g.height))
+    }
+
+    pub fn detection_options(&self) -> DetectionOptions {
+        self.detection_options
+    }
+
+    /// Thresholds used by `detection` when none are given per call.
+    pub fn set_detection_options(&mut self, options: DetectionOptions) {
+        self.detection_options = options;
+    }
+
     pub fn calc_ranges<R>(&self, ranges: R) -> Vec<String> 
     where R: Into<CharsetRange> {
         let charset = match ranges.into() {
@@ -320,7 +783,7 @@ impl<'a> Ddddocr<'a> {
         self.charset_range = self.calc_ranges(ranges)
     }
 
-    pub fn classification_probability_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>, ranges: Option<CharsetRange>) -> anyhow::Result<CharacterProbability>
+    pub fn classification_probability_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>, ranges: Option<CharsetRange>) -> Result<CharacterProbability>
     where I: AsRef<[u8]> {
         let charset_ranges = match ranges {
             Some(v) => self.calc_ranges(v),
@@ -332,84 +795,165 @@ impl<'a> Ddddocr<'a> {
             None => image::load_from_memory(image.as_ref())?,
         };
 
-        let charset_conf = self.charset.as_ref().unwrap();
+        self.classification_probability_image(&image, png_fix, &charset_ranges)
+    }
+
+    /// Reads several images with one session call each for the whole batch.
+    ///
+    /// Images are grouped by width, each group padded on the right to its
+    /// widest image by repeating the last column, and each result is trimmed
+    /// back to its own width, so results can differ marginally from
+    /// one-at-a-time inference. Models exported
+    /// with a fixed batch size of 1 are run image by image under a single lock.
+    pub fn classification_probability_batch<I>(&self, images: &[I], png_fix: bool, filter: Option<ColorFilter>, ranges: Option<CharsetRange>) -> Result<Vec<CharacterProbability>>
+    where I: AsRef<[u8]> {
+        let charset_ranges = match ranges {
+            Some(v) => self.calc_ranges(v),
+            None => self.charset_range.clone(),
+        };
+        let images = images.iter().map(|image| match &filter {
+            Some(v) => v.filter(image.as_ref()),
+            None => Ok(image::load_from_memory(image.as_ref())?),
+        }).collect::<Result<Vec<_>>>()?;
+        let inputs = images.iter().map(|image| self.ocr_input(image, png_fix)).collect::<Result<Vec<_>>>()?;
+        self.ocr_run(inputs, &charset_ranges)
+    }
+
+    pub fn classification_batch_with_options<I>(&self, images: &[I], png_fix: bool, filter: Option<ColorFilter>) -> Result<Vec<String>>
+    where I: AsRef<[u8]> {
+        Ok(self.classification_probability_batch(images, png_fix, filter, None)?
+            .into_iter()
+            .map(|mut p| p.get_text().to_string())
+            .collect())
+    }
+
+    pub(crate) fn classification_probability_image(&self, image: &image::DynamicImage, png_fix: bool, charset_ranges: &[String]) -> Result<CharacterProbability> {
+        let input = self.ocr_input(image, png_fix)?;
+        Ok(self.ocr_run(vec![input], charset_ranges)?.remove(0))
+    }
+
+    /// Resizes and normalizes an image into a `(channel, height, width)` tensor.
+    fn ocr_input(&self, image: &image::DynamicImage, png_fix: bool) -> Result<ndarray::Array3<f32>> {
+        let charset_conf = self.charset.as_ref().ok_or_else(|| DdddocrError::Model("OCR model required".into()))?;
+        let norm = self.normalization.as_ref().ok_or_else(|| DdddocrError::Model("OCR model required".into()))?;
         let resize = charset_conf.image;
         let channel = charset_conf.channel as usize;
 
         let image = if resize[0] == -1 {
              let w = if charset_conf.word { resize[1] as u32 } else { image.width() * resize[1] as u32 / image.height() };
-             image.resize_exact(w, resize[1] as u32, image::imageops::FilterType::Lanczos3)
+             image.resize_exact(w, resize[1] as u32, norm.filter)
         } else {
-            image.resize_exact(resize[0] as u32, resize[1] as u32, image::imageops::FilterType::Lanczos3)
+            image.resize_exact(resize[0] as u32, resize[1] as u32, norm.filter)
         };
 
-        let image_bytes = if channel == 1 {
+        let mut image_bytes = if channel == 1 {
             image.to_luma8().as_raw().clone()
         } else if png_fix {
             png_rgba_black_preprocess(&image).to_rgb8().as_raw().clone()
         } else {
             image.to_rgb8().as_raw().clone()
         };
+        if norm.color_mode == ColorMode::Bgr {
+            image_bytes.chunks_exact_mut(3).for_each(|p| p.swap(0, 2));
+        }
 
         let width = image.width() as usize;
         let height = image.height() as usize;
         let image_arr = ndarray::Array::from_shape_vec((height, width, channel), image_bytes)?;
         // Transpose to (channel, height, width)
         let image_arr = image_arr.permuted_axes([2, 0, 1]);
-        
-        let mut tensor = ndarray::Array::from_shape_vec((1, channel, height, width), vec![0f32; channel*height*width])?;
-
-        for i in 0..height {
-            for j in 0..width {
-                 let now = image_arr[[0, i, j]] as f32;
-                 if self.diy {
-                      if channel == 1 {
-                          tensor[[0, 0, i, j]] = ((now / 255.0) - 0.456) / 0.224;
-                      } else {
-                          tensor[[0, 0, i, j]] = ((image_arr[[0, i, j]] as f32 / 255.0) - 0.485) / 0.229;
-                          tensor[[0, 1, i, j]] = ((image_arr[[1, i, j]] as f32 / 255.0) - 0.456) / 0.224;
-                          tensor[[0, 2, i, j]] = ((image_arr[[2, i, j]] as f32 / 255.0) - 0.406) / 0.225;
-                      }
-                 } else {
-                     tensor[[0, 0, i, j]] = ((now / 255.0) - 0.5) / 0.5;
-                 }
-            }
+
+        Ok(ndarray::Array3::from_shape_fn((channel, height, width), |(c, i, j)| {
+            ((image_arr[[c, i, j]] as f32 / 255.0) - norm.mean[c]) / norm.std[c]
+        }))
+    }
+
+    /// Runs preprocessed images through the model, batching them when it can.
+    fn ocr_run(&self, inputs: Vec<ndarray::Array3<f32>>, charset_ranges: &[String]) -> Result<Vec<CharacterProbability>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
         }
+        let charset_conf = self.charset.as_ref().ok_or_else(|| DdddocrError::Model("OCR model required".into()))?;
+        let charset_len = charset_conf.charset.len();
+        let fixed_batch = self.input_shape.first() == Some(&1);
+        let mut session = self.sessions.get();
+        let widths: Vec<usize> = inputs.iter().map(|v| v.shape()[2]).collect();
 
-        let shape = tensor.shape().to_vec();
-        let data = tensor.into_raw_vec_and_offset().0;
-        let input_value = ort::value::Value::from_array((shape, data))?;
-        let mut session = self.session.lock().unwrap();
-        let outputs = session.run(ort::inputs![input_value])?;
-        let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
-        let shape_usize: Vec<usize> = shape.iter().map(|&v| v as usize).collect();
-        let output = ndarray::ArrayView::from_shape(shape_usize, data)?;
-        
-        // Softmax? Reference code implementation:
-        let output = output.mapv(f32::exp) / output.mapv(f32::exp).sum();
-        let output_sum = output.sum_axis(ndarray::Axis(2));
-        
-        // Normalize
-        let mut probability = ndarray::Array::<f32, _>::zeros(output.raw_dim());
-        for i in 0..output.shape()[0] {
-            let mut a = probability.slice_mut(ndarray::s![i, .., ..]);
-            let b = output.slice(ndarray::s![i, .., ..]);
-            let c = output_sum.slice(ndarray::s![i, ..]);
-            a.assign(&(&b / &c));
+        // Group by width so no image is padded past twice its own width;
+        // one very wide image would otherwise inflate the whole tensor.
+        let batches: Vec<Vec<usize>> = if fixed_batch {
+            (0..inputs.len()).map(|i| vec![i]).collect()
+        } else {
+            let mut order: Vec<usize> = (0..inputs.len()).collect();
+            order.sort_by_key(|&i| widths[i]);
+            let mut batches: Vec<Vec<usize>> = Vec::new();
+            for i in order {
+                match batches.last_mut() {
+                    Some(batch) if widths[i] <= widths[batch[0]].max(1) * 2 => batch.push(i),
+                    _ => batches.push(vec![i]),
+                }
+            }
+            batches
+        };
+
+        let mut logits = vec![None; inputs.len()];
+        for batch in batches {
+            let (channel, height) = (inputs[batch[0]].shape()[0], inputs[batch[0]].shape()[1]);
+            ensure!(batch.iter().all(|&i| inputs[i].shape()[0] == channel && inputs[i].shape()[1] == height), SizeMismatch, "batch images differ in height");
+            let width = batch.iter().map(|&i| widths[i]).max().unwrap();
+            let mut tensor = ndarray::Array4::<f32>::zeros((batch.len(), channel, height, width));
+            for (n, input) in batch.iter().map(|&i| &inputs[i]).enumerate() {
+                let w = input.shape()[2];
+                let mut slot = tensor.index_axis_mut(ndarray::Axis(0), n);
+                slot.slice_mut(ndarray::s![.., .., ..w]).assign(input);
+                if w > 0 && w < width {
+                    let last = input.slice(ndarray::s![.., .., w - 1..w]);
+                    slot.slice_mut(ndarray::s![.., .., w..]).assign(&last.broadcast((channel, height, width - w)).unwrap());
+                }
+            }
+
+            let shape = tensor.shape().to_vec();
+            let data = tensor.into_raw_vec_and_offset().0;
+            let input_value = ort::value::Value::from_array((shape, data))?;
+            let outputs = session.run(ort::inputs![input_value])?;
+            let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
+            let shape_usize: Vec<usize> = shape.iter().map(|&v| v as usize).collect();
+            // Output is (time, batch, class).
+            ensure!(shape_usize.len() == 3, Model, "unexpected OCR output shape {:?}", shape_usize);
+            let output = ndarray::ArrayView3::from_shape((shape_usize[0], shape_usize[1], shape_usize[2]), data)?;
+            ensure!(output.shape()[1] == batch.len(), Model, "model returned {} results for {} images", output.shape()[1], batch.len());
+            ensure!(output.shape()[2] == charset_len, Model, "model has {} classes but the charset has {}", output.shape()[2], charset_len);
+
+            for (n, &i) in batch.iter().enumerate() {
+                let steps = output.shape()[0];
+                let w = widths[i];
+                // Drop the time steps that only saw padding.
+                let steps = if w < width { (steps * w).div_ceil(width).max(1) } else { steps };
+                logits[i] = Some(output.slice(ndarray::s![..steps, n, ..]).to_owned());
+            }
         }
-        
-        let probability = probability.index_axis(ndarray::Axis(1), 0);
+        drop(session);
+
+        Ok(logits.into_iter().flatten().map(|v| self.ocr_probability(v, &charset_conf.charset, charset_ranges)).collect())
+    }
+
+    /// Softmax over classes, then restriction to `charset_ranges` if given.
+    fn ocr_probability(&self, logits: ndarray::Array2<f32>, charset: &[String], charset_ranges: &[String]) -> CharacterProbability {
         let mut result = Vec::new();
-        for row in probability.axis_iter(ndarray::Axis(0)) {
-             result.push(row.into_diag().to_vec());
+        for row in logits.axis_iter(ndarray::Axis(0)) {
+             // Shifting by the row max keeps `exp` from overflowing on large logits.
+             let max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+             let exp = row.mapv(|v| (v - max).exp());
+             let sum = exp.sum();
+             result.push((exp / sum).to_vec());
         }
-        
+
         if charset_ranges.is_empty() {
-            Ok(CharacterProbability { text: None, charset: charset_conf.charset.clone(), probability: result, confidence: None })
+            CharacterProbability::new(charset.to_vec(), result)
         } else {
              let mut indices = Vec::new();
-             for r in &charset_ranges {
-                 indices.push(charset_conf.charset.iter().position(|c| c == r).unwrap_or(usize::MAX));
+             for r in charset_ranges {
+                 indices.push(charset.iter().position(|c| c == r).unwrap_or(usize::MAX));
              }
              let mut filtered = Vec::new();
              for item in &result {
@@ -419,32 +963,62 @@ impl<'a> Ddddocr<'a> {
                  }
                  filtered.push(inner);
              }
-             Ok(CharacterProbability { text: None, charset: charset_ranges, probability: filtered, confidence: None })
+             CharacterProbability::new(charset_ranges.to_vec(), filtered)
         }
     }
 
-    pub fn classification_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>) -> anyhow::Result<String>
+    /// Most probable reading of the image that matches `pattern`, if any.
+    /// `ranges` filters the charset first, exactly as in `classification_probability_with_options`.
+    pub fn classification_pattern_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>, ranges: Option<CharsetRange>, pattern: &TextPattern) -> Result<Option<CtcCandidate>>
+    where I: AsRef<[u8]> {
+        let prob = self.classification_probability_with_options(image, png_fix, filter, ranges)?;
+        Ok(prob.decode_with_pattern(pattern, DEFAULT_BEAM_WIDTH, 1).into_iter().next())
+    }
+
+    /// Reads an arithmetic captcha such as `3+8=?` or `三乘五等于` and evaluates it.
+    pub fn calculation_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>) -> Result<CalcResult>
+    where I: AsRef<[u8]> {
+        let ranges = CharsetRange::Other(CALC_CHARSET.to_string());
+        let mut prob = self.classification_probability_with_options(image, png_fix, filter, Some(ranges))?;
+        calc_text(prob.get_text())
+    }
+
+    pub fn classification_with_options<I>(&self, image: I, png_fix: bool, filter: Option<ColorFilter>) -> Result<String>
     where I: AsRef<[u8]> {
         let prob = self.classification_probability_with_options(image, png_fix, filter, None)?;
         let mut p = prob.clone();
         Ok(p.get_text().to_string())
     }
 
-    pub fn detection<I>(&self, image: I) -> anyhow::Result<Vec<BBox>> where I: AsRef<[u8]> {
-         #[derive(Debug, Clone, Copy)] struct ScoresBBox { scores: f32, x1: f32, y1: f32, x2: f32, y2: f32 }
-         let original = image::load_from_memory(image.as_ref())?;
+    pub fn detection<I>(&self, image: I) -> Result<Vec<BBox>> where I: AsRef<[u8]> {
+         self.detection_image(&image::load_from_memory(image.as_ref())?)
+    }
+
+    /// Like `detection`, with explicit thresholds and each box's score and class.
+    pub fn detection_with_options<I>(&self, image: I, options: DetectionOptions) -> Result<Vec<DetectionBBox>> where I: AsRef<[u8]> {
+         self.detection_image_with_options(&image::load_from_memory(image.as_ref())?, options)
+    }
+
+    pub(crate) fn detection_image(&self, original: &image::DynamicImage) -> Result<Vec<BBox>> {
+         Ok(self.detection_image_with_options(original, self.detection_options)?.iter().map(DetectionBBox::bbox).collect())
+    }
+
+    fn detection_image_with_options(&self, original: &image::DynamicImage, options: DetectionOptions) -> Result<Vec<DetectionBBox>> {
+         #[derive(Debug, Clone, Copy)] struct ScoresBBox { scores: f32, class_id: u32, x1: f32, y1: f32, x2: f32, y2: f32 }
+         let grid = self.detection_grid.as_ref().ok_or_else(|| DdddocrError::Model("not a detection model".into()))?;
+         let (model_w, model_h) = (grid.width, grid.height);
          let (orig_w, orig_h) = original.dimensions();
-         let x_scale = MODEL_WIDTH as f32 / orig_w as f32;
-         let y_scale = MODEL_HEIGHT as f32 / orig_h as f32;
+         let x_scale = model_w as f32 / orig_w as f32;
+         let y_scale = model_h as f32 / orig_h as f32;
          let gain = x_scale.min(y_scale);
          let resize_w = (orig_w as f32 * gain) as u32;
          let resize_h = (orig_h as f32 * gain) as u32;
          
          let image = original.resize_exact(resize_w, resize_h, image::imageops::FilterType::Triangle).to_rgb8();
-         let mut canvas = image::ImageBuffer::from_pixel(MODEL_WIDTH, MODEL_HEIGHT, image::Rgb([114, 114, 114]));
+         let mut canvas = image::ImageBuffer::from_pixel(model_w, model_h, image::Rgb([114, 114, 114]));
          image::imageops::overlay(&mut canvas, &image, 0, 0);
          
-         let mut input_tensor = ndarray::Array::from_shape_vec((1, 3, MODEL_HEIGHT as usize, MODEL_WIDTH as usize), vec![0f32; 3 * MODEL_HEIGHT as usize * MODEL_WIDTH as usize])?;
+         let mut input_tensor = ndarray::Array::from_shape_vec((1, 3, model_h as usize, model_w as usize), vec![0f32; 3 * model_h as usize * model_w as usize])?;
          
          for (x, y, p) in canvas.enumerate_pixels() {
              // Reference: x and y might be swapped in tensor assignment in original code?
@@ -460,24 +1034,35 @@ impl<'a> Ddddocr<'a> {
          let shape = input_tensor.shape().to_vec();
          let data = input_tensor.into_raw_vec_and_offset().0;
          let input_value = ort::value::Value::from_array((shape, data))?;
-         let mut session = self.session.lock().unwrap();
+         let mut session = self.sessions.get();
          let outputs = session.run(ort::inputs![input_value])?;
          let (shape, data) = outputs[0].try_extract_tensor::<f32>()?;
          let shape_usize: Vec<usize> = shape.iter().map(|&v| v as usize).collect();
          let output = ndarray::ArrayView::from_shape(shape_usize, data)?;
          
          let mut boxes = Vec::new();
-         let num_boxes = output.len() / 6;
+         // Each row is [cx, cy, w, h, objectness, class probabilities...].
+         let columns = output.shape().last().copied().unwrap_or(0);
+         ensure!(output.ndim() == 3 && columns > 5, Model, "unexpected detection output shape {:?}", output.shape());
+         let num_boxes = output.shape()[1];
+         ensure!(num_boxes == grid.expanded_strides.len(), Model, "model output has {} boxes, expected {} for {}x{}", num_boxes, grid.expanded_strides.len(), model_w, model_h);
          for i in 0..num_boxes {
-             let score = output[[0, i, 4]] * output[[0, i, 5]];
-             if score < SCORE_THR { continue; }
-             let x1 = (output[[0, i, 0]] + GRIDS[i * 2] as f32) * EXPANDED_STRIDES[i] as f32;
-             let y1 = (output[[0, i, 1]] + GRIDS[i * 2 + 1] as f32) * EXPANDED_STRIDES[i] as f32;
-             let x2 = output[[0, i, 2]].exp() * EXPANDED_STRIDES[i] as f32;
-             let y2 = output[[0, i, 3]].exp() * EXPANDED_STRIDES[i] as f32;
+             let (class_id, class_prob) = (5..columns)
+                 .map(|c| output[[0, i, c]])
+                 .enumerate()
+                 .max_by(|a, b| a.1.total_cmp(&b.1))
+                 .unwrap();
+             let score = output[[0, i, 4]] * class_prob;
+             if score < options.score_threshold { continue; }
+             let stride = grid.expanded_strides[i] as f32;
+             let x1 = (output[[0, i, 0]] + grid.grids[i * 2] as f32) * stride;
+             let y1 = (output[[0, i, 1]] + grid.grids[i * 2 + 1] as f32) * stride;
+             let x2 = output[[0, i, 2]].exp() * stride;
+             let y2 = output[[0, i, 3]].exp() * stride;
              
              boxes.push(ScoresBBox {
                  scores: score,
+                 class_id: class_id as u32,
                  x1: (x1 - x2 / 2.0) / gain,
                  y1: (y1 - y2 / 2.0) / gain,
                  x2: (x1 + x2 / 2.0) / gain,
@@ -502,24 +1087,53 @@ impl<'a> Ddddocr<'a> {
                  let area1 = (current.x2 - current.x1 + 1.0) * (current.y2 - current.y1 + 1.0);
                  let area2 = (b.x2 - b.x1 + 1.0) * (b.y2 - b.y1 + 1.0);
                  let iou = inter / (area1 + area2 - inter);
-                 iou <= NMS_THR
+                 iou <= options.nms_threshold
              });
          }
 
-         Ok(result.into_iter().map(|b| BBox {
+         Ok(result.into_iter().map(|b| DetectionBBox {
              x1: b.x1.max(0.0).min(orig_w as f32 - 1.0) as u32,
              y1: b.y1.max(0.0).min(orig_h as f32 - 1.0) as u32,
              x2: b.x2.max(0.0).min(orig_w as f32 - 1.0) as u32,
              y2: b.y2.max(0.0).min(orig_h as f32 - 1.0) as u32,
+             score: b.scores,
+             class_id: b.class_id,
          }).collect())
     }
 }
 
-pub fn slide_match<I1, I2>(target: I1, bg: I2) -> anyhow::Result<SlideBBox> 
+/// Best `top_n` placements of `t_edge` in `b_edge`, highest score first.
+/// Candidates never overlap, so runner-ups are real alternatives rather
+/// than the best hit shifted by a pixel.
+fn match_edges(t_edge: &image::GrayImage, b_edge: &image::GrayImage, top_n: usize, target_x: u32, target_y: u32) -> Result<Vec<SlideBBox>> {
+    let (tw, th) = t_edge.dimensions();
+    ensure!(b_edge.width() >= tw && b_edge.height() >= th, SizeMismatch, "bg too small");
+    let res = imageproc::template_matching::match_template(b_edge, t_edge, imageproc::template_matching::MatchTemplateMethod::CrossCorrelationNormalized);
+
+    let mut positions: Vec<(u32, u32, f32)> = res.enumerate_pixels()
+        .map(|(x, y, p)| (x, y, if p[0].is_nan() { 0.0 } else { p[0] }))
+        .collect();
+    positions.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let mut result: Vec<SlideBBox> = Vec::new();
+    for (x, y, score) in positions {
+        if result.len() >= top_n.max(1) { break; }
+        if result.iter().any(|b| x.abs_diff(b.x1) < tw && y.abs_diff(b.y1) < th) { continue; }
+        result.push(SlideBBox { target_x, target_y, x1: x, y1: y, x2: x + tw, y2: y + th, score });
+    }
+    Ok(result)
+}
+
+pub fn slide_match<I1, I2>(target: I1, bg: I2) -> Result<SlideBBox> 
+where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
+    Ok(slide_match_candidates(target, bg, 1)?.remove(0))
+}
+
+pub fn slide_match_candidates<I1, I2>(target: I1, bg: I2, top_n: usize) -> Result<Vec<SlideBBox>> 
 where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
     let target = image::load_from_memory(target.as_ref())?;
     let bg = image::load_from_memory(bg.as_ref())?;
-    anyhow::ensure!(bg.width() >= target.width() && bg.height() >= target.height(), "bg too small");
+    ensure!(bg.width() >= target.width() && bg.height() >= target.height(), SizeMismatch, "bg too small");
     
     let target = target.to_rgba8();
     let (w, h) = target.dimensions();
@@ -542,59 +1156,401 @@ where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
     
     let t_edge = imageproc::edges::canny(&image::imageops::grayscale(&crop), 100.0, 200.0);
     let b_edge = imageproc::edges::canny(&bg.to_luma8(), 100.0, 200.0);
-    let res = imageproc::template_matching::match_template(&b_edge, &t_edge, imageproc::template_matching::MatchTemplateMethod::CrossCorrelationNormalized);
-    let extremes = imageproc::template_matching::find_extremes(&res);
-    
-    Ok(SlideBBox {
-        target_x: min_x, target_y: min_y,
-        x1: extremes.max_value_location.0, y1: extremes.max_value_location.1,
-        x2: extremes.max_value_location.0 + t_edge.width(), y2: extremes.max_value_location.1 + t_edge.height()
-    })
+    match_edges(&t_edge, &b_edge, top_n, min_x, min_y)
+}
+
+pub fn simple_slide_match<I1, I2>(target: I1, bg: I2) -> Result<SlideBBox> 
+where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
+    Ok(simple_slide_match_candidates(target, bg, 1)?.remove(0))
 }
 
-pub fn simple_slide_match<I1, I2>(target: I1, bg: I2) -> anyhow::Result<SlideBBox> 
+pub fn simple_slide_match_candidates<I1, I2>(target: I1, bg: I2, top_n: usize) -> Result<Vec<SlideBBox>> 
 where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
     let target = image::load_from_memory(target.as_ref())?;
     let bg = image::load_from_memory(bg.as_ref())?;
     
     let t_edge = imageproc::edges::canny(&target.to_luma8(), 100.0, 200.0);
     let b_edge = imageproc::edges::canny(&bg.to_luma8(), 100.0, 200.0);
-    let res = imageproc::template_matching::match_template(&b_edge, &t_edge, imageproc::template_matching::MatchTemplateMethod::CrossCorrelationNormalized);
-    let extremes = imageproc::template_matching::find_extremes(&res);
-    
-    Ok(SlideBBox {
-        target_x: 0, target_y: 0,
-        x1: extremes.max_value_location.0, y1: extremes.max_value_location.1,
-        x2: extremes.max_value_location.0 + t_edge.width(), y2: extremes.max_value_location.1 + t_edge.height()
-    })
+    match_edges(&t_edge, &b_edge, top_n, 0, 0)
+}
+
+/// Largest background, in pixels, `slide_gap_match` searches.
+pub const MAX_GAP_SEARCH_PIXELS: u64 = 4_000_000;
+
+/// Finds the notch in a slider background that comes without a piece image.
+///
+/// The cut-out is drawn as a shadowed or bevelled square, so every square
+/// window is scored by the edge strength along its four sides (the weakest
+/// three count, so a single strong line is not enough) and by how much its
+/// inside differs from a thin ring around it. `size` fixes the notch's
+/// width and height; otherwise up to 16 square sizes from 15% to 45% of
+/// the image height are tried. Backgrounds are limited to
+/// `MAX_GAP_SEARCH_PIXELS`, which bounds the search.
+pub fn slide_gap_match<I>(bg: I, size: Option<(u32, u32)>) -> Result<SlideBBox>
+where I: AsRef<[u8]> {
+    const RING: u32 = 4;
+    let bg = image::load_from_memory(bg.as_ref())?;
+    let (w, h) = bg.dimensions();
+    ensure!(w as u64 * h as u64 <= MAX_GAP_SEARCH_PIXELS, InvalidArgument,
+        "background is {}x{}, more than {} pixels", w, h, MAX_GAP_SEARCH_PIXELS);
+    // The notch needs an inside and a ring of background on every side.
+    let fits = |bw: u32, bh: u32| {
+        bw > 2 * RING && bh > 2 * RING
+            && bw.checked_add(2 * RING).is_some_and(|v| v < w)
+            && bh.checked_add(2 * RING).is_some_and(|v| v < h)
+    };
+    if let Some((bw, bh)) = size {
+        ensure!(fits(bw, bh), InvalidArgument,
+            "gap size {}x{} must exceed {} and leave a {} pixel margin in the {}x{} background", bw, bh, 2 * RING, RING, w, h);
+    }
+    let luma = imageproc::filter::gaussian_blur_f32(&bg.to_luma8(), 1.0);
+    let gx = imageproc::gradients::horizontal_sobel(&luma);
+    let gy = imageproc::gradients::vertical_sobel(&luma);
+    let (wu, hu) = (w as usize, h as usize);
+
+    // Running sums: |gx| down each column, |gy| along each row, luma over area.
+    let mut col = vec![0f32; (hu + 1) * wu];
+    let mut row = vec![0f32; hu * (wu + 1)];
+    let mut area = vec![0f32; (hu + 1) * (wu + 1)];
+    let mut max_edge = 1f32;
+    for y in 0..hu {
+        for x in 0..wu {
+            let ex = (gx.get_pixel(x as u32, y as u32)[0] as f32).abs();
+            let ey = (gy.get_pixel(x as u32, y as u32)[0] as f32).abs();
+            max_edge = max_edge.max(ex).max(ey);
+            col[(y + 1) * wu + x] = col[y * wu + x] + ex;
+            row[y * (wu + 1) + x + 1] = row[y * (wu + 1) + x] + ey;
+            area[(y + 1) * (wu + 1) + x + 1] = area[y * (wu + 1) + x + 1] + area[(y + 1) * (wu + 1) + x]
+                - area[y * (wu + 1) + x] + luma.get_pixel(x as u32, y as u32)[0] as f32;
+        }
+    }
+    let col_sum = |x: u32, y0: u32, y1: u32| col[y1 as usize * wu + x as usize] - col[y0 as usize * wu + x as usize];
+    let row_sum = |y: u32, x0: u32, x1: u32| row[y as usize * (wu + 1) + x1 as usize] - row[y as usize * (wu + 1) + x0 as usize];
+    let area_sum = |x0: u32, y0: u32, x1: u32, y1: u32| {
+        let at = |x: u32, y: u32| area[y as usize * (wu + 1) + x as usize];
+        at(x1, y1) - at(x0, y1) - at(x1, y0) + at(x0, y0)
+    };
+
+    let sizes: Vec<(u32, u32)> = match size {
+        Some(v) => vec![v],
+        None => {
+            let (lo, hi) = (((h as f32 * 0.15) as u32).max(2 * RING + 1), (h as f32 * 0.45) as u32);
+            let step = (hi.saturating_sub(lo) / 16 + 1).max(2) as usize;
+            (lo..=hi).step_by(step).map(|v| (v, v)).collect()
+        }
+    };
+
+    let mut best: Option<(f32, SlideBBox)> = None;
+    for (bw, bh) in sizes {
+        if !fits(bw, bh) { continue; }
+        for y in RING..h - bh - RING {
+            for x in RING..w - bw - RING {
+                let mut sides = [
+                    col_sum(x, y, y + bh) / bh as f32,
+                    col_sum(x + bw - 1, y, y + bh) / bh as f32,
+                    row_sum(y, x, x + bw) / bw as f32,
+                    row_sum(y + bh - 1, x, x + bw) / bw as f32,
+                ];
+                sides.sort_by(f32::total_cmp);
+                let edge = (sides[0] + sides[1] + sides[2]) / 3.0 / max_edge;
+
+                let inner = area_sum(x + RING, y + RING, x + bw - RING, y + bh - RING);
+                let inner_n = ((bw - 2 * RING) * (bh - 2 * RING)).max(1) as f32;
+                let outer = area_sum(x - RING, y - RING, x + bw + RING, y + bh + RING) - area_sum(x, y, x + bw, y + bh);
+                let outer_n = ((bw + 2 * RING) * (bh + 2 * RING) - bw * bh) as f32;
+                let contrast = (outer / outer_n - inner / inner_n).abs() / 255.0;
+
+                let score = 0.7 * edge + 0.3 * contrast;
+                if best.as_ref().is_none_or(|(s, _)| score > *s) {
+                    best = Some((score, SlideBBox {
+                        target_x: 0, target_y: 0,
+                        x1: x, y1: y, x2: x + bw, y2: y + bh,
+                        score: score.clamp(0.0, 1.0),
+                    }));
+                }
+            }
+        }
+    }
+    best.map(|(_, b)| b).ok_or_else(|| DdddocrError::SizeMismatch("bg too small for the gap size".into()))
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct CompareOptions {
+    /// Per-channel difference above which a pixel counts as changed.
+    pub diff_threshold: u8,
+    /// Changed pixels must form a horizontal or vertical run at least this long,
+    /// which filters out compression noise.
+    pub min_run: u32,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self { Self { diff_threshold: 80, min_run: 5 } }
 }
 
-pub fn slide_comparison<I1, I2>(target: I1, bg: I2) -> anyhow::Result<(u32, u32)> 
+/// Compares a background with and without the gap and returns the gap's
+/// bounding box, or `None` if the images do not differ. Like `SlideBBox`,
+/// `x2`/`y2` are exclusive.
+pub fn slide_comparison_with_options<I1, I2>(target: I1, bg: I2, options: CompareOptions) -> Result<Option<BBox>>
 where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
     let t = image::load_from_memory(target.as_ref())?.to_rgb8();
     let b = image::load_from_memory(bg.as_ref())?.to_rgb8();
-    anyhow::ensure!(t.dimensions() == b.dimensions(), "dimensions mismatch");
+    ensure!(t.dimensions() == b.dimensions(), SizeMismatch, "dimensions mismatch");
+    let (w, h) = t.dimensions();
+    let threshold = options.diff_threshold as i16;
+    let min_run = options.min_run.max(1);
     
-    let diff_img = image::RgbImage::from_fn(t.width(), t.height(), |x, y| {
+    let diff = |x: u32, y: u32| {
         let p1 = t.get_pixel(x, y);
         let p2 = b.get_pixel(x, y);
-        // Simple diff
-        if (p1[0] as i16 - p2[0] as i16).abs() > 80 || 
-           (p1[1] as i16 - p2[1] as i16).abs() > 80 || 
-           (p1[2] as i16 - p2[2] as i16).abs() > 80 {
-            image::Rgb([255, 255, 255])
-        } else {
-            image::Rgb([0, 0, 0])
+        (0..3).any(|c| (p1[c] as i16 - p2[c] as i16).abs() > threshold)
+    };
+    let changed = image::GrayImage::from_fn(w, h, |x, y| image::Luma([diff(x, y) as u8]));
+
+    // Keep only changed pixels that sit on a long enough run in either direction.
+    let mut kept = image::GrayImage::new(w, h);
+    let mut mark_runs = |len: u32, at: &dyn Fn(u32) -> (u32, u32)| {
+        let mut start = 0;
+        for i in 0..=len {
+            if i < len && changed[at(i)][0] != 0 { continue; }
+            if i - start >= min_run {
+                for j in start..i { kept[at(j)] = image::Luma([255]); }
+            }
+            start = i + 1;
         }
-    });
-    
-    for x in 0..diff_img.width() {
-        let mut count = 0;
-        for y in 0..diff_img.height() {
-            if diff_img[(x, y)] == image::Rgb([255, 255, 255]) { count += 1; }
-            if count >= 5 { return Ok((x, y.saturating_sub(5))); }
+    };
+    for x in 0..w { mark_runs(h, &|y| (x, y)); }
+    for y in 0..h { mark_runs(w, &|x| (x, y)); }
+
+    let labels = imageproc::region_labelling::connected_components(&kept, imageproc::region_labelling::Connectivity::Eight, image::Luma([0u8]));
+    let mut regions: std::collections::HashMap<u32, (u32, BBox)> = std::collections::HashMap::new();
+    for (x, y, l) in labels.enumerate_pixels() {
+        if l[0] == 0 { continue; }
+        let (count, bbox) = regions.entry(l[0]).or_insert((0, BBox { x1: x, y1: y, x2: x, y2: y }));
+        *count += 1;
+        bbox.x1 = bbox.x1.min(x);
+        bbox.y1 = bbox.y1.min(y);
+        bbox.x2 = bbox.x2.max(x);
+        bbox.y2 = bbox.y2.max(y);
+    }
+    Ok(regions.into_values().max_by_key(|(count, _)| *count).map(|(_, bbox)| BBox { x2: bbox.x2 + 1, y2: bbox.y2 + 1, ..bbox }))
+}
+
+/// Top-left corner of the gap with the default thresholds, or `(0, 0)` when
+/// nothing differs. Prefer `slide_comparison_with_options`, which tells the two apart.
+pub fn slide_comparison<I1, I2>(target: I1, bg: I2) -> Result<(u32, u32)> 
+where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
+    Ok(slide_comparison_with_options(target, bg, CompareOptions::default())?
+        .map(|b| (b.x1, b.y1))
+        .unwrap_or((0, 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(image: image::RgbImage) -> Vec<u8> {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    /// A textured background with a dark 40x40 notch at (120, 40).
+    fn notched(w: u32, h: u32) -> Vec<u8> {
+        png(image::RgbImage::from_fn(w, h, |x, y| {
+            if (120..160).contains(&x) && (40..80).contains(&y) {
+                image::Rgb([70, 70, 70])
+            } else {
+                let v = 170 + ((x * 7 + y * 3) % 30) as u8;
+                image::Rgb([v, v, v])
+            }
+        }))
+    }
+
+    #[test]
+    fn gap_match_finds_notch() {
+        for size in [Some((40, 40)), None] {
+            let res = slide_gap_match(notched(300, 150), size).unwrap();
+            assert_eq!((res.x1, res.y1, res.x2, res.y2), (120, 40, 160, 80), "{:?}", size);
+        }
+    }
+
+    #[test]
+    fn gap_match_rejects_bad_sizes() {
+        let bg = notched(300, 150);
+        for size in [(8, 40), (40, 8), (0, 0), (292, 40), (40, 142), (u32::MAX, 40), (40, u32::MAX)] {
+            assert!(matches!(slide_gap_match(&bg, Some(size)), Err(DdddocrError::InvalidArgument(_))), "{:?}", size);
+        }
+        assert!(slide_gap_match(&bg, Some((291, 141))).is_ok());
+        let tiny = png(image::RgbImage::new(20, 12));
+        assert!(matches!(slide_gap_match(tiny, None), Err(DdddocrError::SizeMismatch(_))));
+    }
+
+    #[test]
+    fn gap_match_bounds_background_pixels() {
+        let side = (MAX_GAP_SEARCH_PIXELS as f64).sqrt() as u32;
+        let bg = png(image::RgbImage::new(side + 1, side));
+        assert!(matches!(slide_gap_match(bg, Some((40, 40))), Err(DdddocrError::InvalidArgument(_))));
+    }
+
+    /// A plain background, and the same with a 20x20 gap at (40, 10) plus a 3 px speck.
+    fn comparison_pair() -> (Vec<u8>, Vec<u8>) {
+        let bg = image::RgbImage::from_pixel(100, 50, image::Rgb([200, 200, 200]));
+        let mut gap = bg.clone();
+        for (x, y, p) in gap.enumerate_pixels_mut() {
+            if (40..60).contains(&x) && (10..30).contains(&y) || (80..83).contains(&x) && y == 40 {
+                *p = image::Rgb([40, 40, 40]);
+            }
+        }
+        (png(gap), png(bg))
+    }
+
+    #[test]
+    fn comparison_returns_exclusive_box() {
+        let (gap, bg) = comparison_pair();
+        let b = slide_comparison_with_options(&gap, &bg, CompareOptions::default()).unwrap().unwrap();
+        assert_eq!((b.x1, b.y1, b.x2, b.y2), (40, 10, 60, 30));
+        assert_eq!(slide_comparison(&gap, &bg).unwrap(), (40, 10));
+    }
+
+    #[test]
+    fn comparison_of_identical_images_is_none() {
+        let (_, bg) = comparison_pair();
+        assert!(slide_comparison_with_options(&bg, &bg, CompareOptions::default()).unwrap().is_none());
+        assert_eq!(slide_comparison(&bg, &bg).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn comparison_drops_runs_shorter_than_min_run() {
+        let (gap, bg) = comparison_pair();
+        // Runs of 21 and more leave nothing; the 3 px speck only survives a min_run of 3.
+        let options = |min_run| CompareOptions { min_run, ..Default::default() };
+        assert!(slide_comparison_with_options(&gap, &bg, options(21)).unwrap().is_none());
+        let b = slide_comparison_with_options(&gap, &bg, options(20)).unwrap().unwrap();
+        assert_eq!((b.x1, b.y1, b.x2, b.y2), (40, 10, 60, 30));
+        let speck = |min_run| {
+            let only_speck = png(image::RgbImage::from_fn(100, 50, |x, y| {
+                if (80..83).contains(&x) && y == 40 { image::Rgb([40, 40, 40]) } else { image::Rgb([200, 200, 200]) }
+            }));
+            slide_comparison_with_options(only_speck, &bg, options(min_run)).unwrap()
+        };
+        assert!(speck(4).is_none());
+        let b = speck(3).unwrap();
+        assert_eq!((b.x1, b.y1, b.x2, b.y2), (80, 40, 83, 41));
+    }
+
+    /// A 24x24 piece: a bright square with a notch cut into its right side.
+    fn piece(x: u32, y: u32) -> bool {
+        (6..18).contains(&x) && (6..18).contains(&y) && !((14..18).contains(&x) && (10..14).contains(&y))
+    }
+
+    #[test]
+    fn edge_match_returns_distinct_candidates_in_score_order() {
+        let target = png(image::RgbImage::from_fn(24, 24, |x, y| {
+            if piece(x, y) { image::Rgb([255, 255, 255]) } else { image::Rgb([0, 0, 0]) }
+        }));
+        // An exact copy at (30, 20), and one at (130, 12) with its notch filled in.
+        let bg = png(image::RgbImage::from_fn(200, 60, |x, y| {
+            let exact = x >= 30 && y >= 20 && x < 54 && y < 44 && piece(x - 30, y - 20);
+            let near = x >= 130 && y >= 12 && x < 154 && y < 36 && (piece(x - 130, y - 12) || (14..18).contains(&(x - 130)) && (10..14).contains(&(y - 12)));
+            if exact || near { image::Rgb([255, 255, 255]) } else { image::Rgb([0, 0, 0]) }
+        }));
+
+        let res = simple_slide_match_candidates(&target, &bg, 3).unwrap();
+        assert_eq!(res.len(), 3);
+        assert_eq!((res[0].x1, res[0].y1, res[0].x2, res[0].y2), (30, 20, 54, 44));
+        assert_eq!((res[1].x1, res[1].y1, res[1].x2, res[1].y2), (130, 12, 154, 36));
+        assert!(res.windows(2).all(|v| v[0].score >= v[1].score), "{:?}", res);
+        for (i, a) in res.iter().enumerate() {
+            for b in &res[i + 1..] {
+                assert!(a.x2 <= b.x1 || b.x2 <= a.x1 || a.y2 <= b.y1 || b.y2 <= a.y1, "{:?} overlaps {:?}", a, b);
+            }
+        }
+        assert_eq!(simple_slide_match_candidates(&target, &bg, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parses_colors() {
+        for (name, color) in Color::NAMES.iter().zip([Color::Red, Color::Blue, Color::Green, Color::Yellow, Color::Orange, Color::Purple, Color::Cyan, Color::Black, Color::White, Color::Gray]) {
+            assert_eq!(name.parse::<Color>(), Ok(color));
+        }
+        assert_eq!("Red".parse::<Color>(), Ok(Color::Red));
+        assert_eq!("purpel".parse::<Color>(), Err(ParseColorError("purpel".into())));
+        assert_eq!("".parse::<Color>(), Err(ParseColorError("".into())));
+    }
+
+    #[test]
+    fn invalid_color_filter_reports_the_color() {
+        assert!(matches!(serde_json::from_str::<ColorFilter>(r#"["red","blue"]"#), Ok(ColorFilter::ColorRanges(v)) if v == [Color::Red, Color::Blue]));
+        for json in [r#""purpel""#, r#"["red","purpel"]"#] {
+            let err = serde_json::from_str::<ColorFilter>(json).unwrap_err().to_string();
+            assert!(err.contains(&ParseColorError("purpel".into()).to_string()), "{}", err);
+        }
+    }
+
+    #[test]
+    fn parses_charset_ranges() {
+        for digit in 0..8 {
+            let parsed = digit.to_string().parse::<CharsetRange>().unwrap();
+            let preset = CharsetRange::try_from(digit).unwrap();
+            assert_eq!(format!("{:?}", parsed), format!("{:?}", preset));
+            assert!(!matches!(parsed, CharsetRange::Other(_)), "{:?}", parsed);
+        }
+        assert!(matches!("0".parse(), Ok(CharsetRange::Digit)));
+        assert!(matches!("7".parse(), Ok(CharsetRange::DefaultCharsetLowercaseUppercaseDigit)));
+        assert!(matches!(CharsetRange::try_from(8), Err(ParseCharsetRangeError::Preset(8))));
+        assert!(matches!(CharsetRange::try_from(-1), Err(ParseCharsetRangeError::Preset(-1))));
+        // A lone 8 or 9 is not a preset, so it lists that single character.
+        assert!(matches!("8".parse(), Ok(CharsetRange::Other(v)) if v == "8"));
+        assert!(matches!("".parse::<CharsetRange>(), Err(ParseCharsetRangeError::Empty)));
+        assert!(matches!("0123456789+-".parse(), Ok(CharsetRange::Other(v)) if v == "0123456789+-"));
+        assert!(matches!("07".parse(), Ok(CharsetRange::Other(v)) if v == "07"));
+    }
+
+    /// A three-symbol charset with `extra` merged into its JSON.
+    fn charset(channel: i64, extra: serde_json::Value) -> Charset {
+        let mut json = serde_json::json!({ "image": [-1, 64], "channel": channel, "charset": ["", "a", "b"] });
+        json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn normalization_falls_back_to_the_hash_guess() {
+        let gray = charset(1, serde_json::json!({})).normalization(false).unwrap();
+        assert_eq!((gray.mean, gray.std, gray.color_mode), (vec![0.5], vec![0.5], ColorMode::Gray));
+        let gray = charset(1, serde_json::json!({})).normalization(true).unwrap();
+        assert_eq!((gray.mean, gray.std), (vec![0.456], vec![0.224]));
+        let rgb = charset(3, serde_json::json!({})).normalization(false).unwrap();
+        assert_eq!((rgb.mean, rgb.std, rgb.color_mode), (vec![0.5; 3], vec![0.5; 3], ColorMode::Rgb));
+        let rgb = charset(3, serde_json::json!({})).normalization(true).unwrap();
+        assert_eq!((rgb.mean, rgb.std), (vec![0.485, 0.456, 0.406], vec![0.229, 0.224, 0.225]));
+    }
+
+    #[test]
+    fn normalization_uses_declared_values() {
+        let declared = serde_json::json!({ "mean": [0.1, 0.2, 0.3], "std": [0.4], "color_mode": "bgr" });
+        for diy in [false, true] {
+            let norm = charset(3, declared.clone()).normalization(diy).unwrap();
+            assert_eq!((norm.mean, norm.std, norm.color_mode), (vec![0.1, 0.2, 0.3], vec![0.4; 3], ColorMode::Bgr));
+        }
+    }
+
+    #[test]
+    fn normalization_rejects_inconsistent_charsets() {
+        for (channel, extra) in [
+            (1, serde_json::json!({ "mean": [0.5] })),
+            (1, serde_json::json!({ "std": [0.5] })),
+            (3, serde_json::json!({ "mean": [0.1, 0.2], "std": [0.5] })),
+            (1, serde_json::json!({ "mean": [0.5], "std": [0.1, 0.2, 0.3] })),
+            (3, serde_json::json!({ "mean": [0.5], "std": [0.2, 0.0, 0.2] })),
+            (1, serde_json::json!({ "mean": [0.5], "std": [0.0] })),
+            (1, serde_json::json!({ "color_mode": "rgb" })),
+            (1, serde_json::json!({ "color_mode": "bgr" })),
+            (3, serde_json::json!({ "color_mode": "gray" })),
+        ] {
+            for diy in [false, true] {
+                let res = charset(channel, extra.clone()).normalization(diy);
+                assert!(matches!(res, Err(DdddocrError::Charset(_))), "{} {} {:?}", channel, extra, res);
+            }
         }
     }
-    
-    Ok((0, 0))
 }
@@ -1,8 +1,10 @@
 use clap::Parser;
 use ddddocr_musl::*;
 use salvo::prelude::*;
-use salvo::oapi::extract::JsonBody;
+use salvo::oapi::extract::{JsonBody, PathParam};
 use base64::prelude::*;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
 use std::path::PathBuf;
 use std::sync::OnceLock;
 use anyhow::Context;
@@ -13,16 +15,40 @@ use tokio::sync::Mutex;
 use tokio::sync::RwLock;
 use std::sync::LazyLock;
 use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 use salvo::catcher::Catcher;
 
 // Global singletons
 static ARGS: OnceLock<Args> = OnceLock::new();
-static OCR: LazyLock<RwLock<Option<Arc<Ddddocr<'static>>>>> = LazyLock::new(|| RwLock::new(None));
-static DET: LazyLock<RwLock<Option<Arc<Ddddocr<'static>>>>> = LazyLock::new(|| RwLock::new(None));
+static OCR: LazyLock<RwLock<Option<Arc<LoadedModel>>>> = LazyLock::new(|| RwLock::new(None));
+static DET: LazyLock<RwLock<Option<Arc<LoadedModel>>>> = LazyLock::new(|| RwLock::new(None));
+/// Named models from `--model-dir`, selectable per request.
+static MODELS: LazyLock<RwLock<BTreeMap<String, Arc<LoadedModel>>>> = LazyLock::new(|| RwLock::new(BTreeMap::new()));
 static SLIDE_ENABLED: AtomicBool = AtomicBool::new(true);
 static CACHE: LazyLock<Mutex<LruCache<String, Vec<String>>>> =
     LazyLock::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(10).unwrap())));
+static PATTERN_CACHE: LazyLock<Mutex<LruCache<String, Arc<TextPattern>>>> =
+    LazyLock::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(10).unwrap())));
+
+/// A model together with where and when it was loaded.
+struct LoadedModel {
+    instance: Ddddocr<'static>,
+    path: PathBuf,
+    loaded_at: std::time::SystemTime,
+}
+
+impl LoadedModel {
+    fn new(instance: Ddddocr<'static>, path: &Path) -> Self {
+        Self { instance, path: path.to_path_buf(), loaded_at: std::time::SystemTime::now() }
+    }
+}
+
+impl std::ops::Deref for LoadedModel {
+    type Target = Ddddocr<'static>;
+    fn deref(&self) -> &Self::Target { &self.instance }
+}
 
 #[derive(Parser, Debug, Clone)]
 struct Args {
@@ -48,33 +74,243 @@ struct Args {
     #[arg(long)]
     disable_slide: bool,
 
+    /// Directory of extra models. Every `<name>.onnx` becomes model `<name>`:
+    /// an OCR model if `<name>.json` exists next to it, a detector otherwise.
+    #[arg(long)]
+    model_dir: Option<PathBuf>,
+
+    /// Default charset range of the `--ocr-path` model only.
     #[arg(long)]
     ocr_charset_range: Option<String>,
+
+    /// Only load models with this SHA-256 (hex). Repeat for several; when
+    /// none are given, any model is accepted.
+    #[arg(long = "pin-sha256")]
+    pinned_sha256: Vec<String>,
+
+    /// Check the default model files every N seconds and reload them when they
+    /// change. 0 disables the watcher; SIGHUP always triggers a reload.
+    #[arg(long, default_value_t = 0)]
+    watch_interval: u64,
+
+    /// Bearer token for the `/admin` endpoints. They are disabled when unset.
+    #[arg(long)]
+    admin_token: Option<String>,
+
+    /// Inference sessions per model; requests beyond this many wait for a free one.
+    #[arg(long, default_value_t = 1)]
+    pool_size: usize,
+
+    /// Intra-op threads per session. Defaults to the backend's choice.
+    #[arg(long)]
+    intra_threads: Option<usize>,
+
+    /// Most images one `/ocr/batch` request may carry.
+    #[arg(long, default_value_t = 32)]
+    max_batch: usize,
+}
+
+impl Args {
+    fn session_options(&self) -> SessionOptions {
+        SessionOptions { pool_size: self.pool_size.max(1), intra_threads: self.intra_threads }
+    }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 struct OCRRequest {
+    #[serde(default)]
     image: String,
+    #[serde(flatten)]
+    options: OCROptions,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct OCRBatchRequest {
+    #[serde(default)]
+    images: Vec<String>,
+    /// Applied to every image.
+    #[serde(flatten)]
+    options: OCROptions,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct OCROptions {
+    /// Registered model name, see `/models`. Defaults to the `--ocr-path` model.
+    model: Option<String>,
     png_fix: Option<bool>,
     probability: Option<bool>,
     charset_range: Option<String>,
     color_filter: Option<serde_json::Value>,
+    /// Defaults to beam search when `top_k` > 1.
+    decoder: Option<DecoderKind>,
+    /// At most 64.
+    beam_width: Option<usize>,
+    /// Number of alternative strings to return, best first. At most 20.
+    top_k: Option<usize>,
+    /// Regex the text must match, e.g. "^[0-9]{4}$". Implies beam search.
+    /// No match yields an empty text with zero confidence.
+    pattern: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum DecoderKind {
+    Greedy,
+    BeamSearch,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 struct OCRResponse {
     text: String,
+    confidence: f64,
+    char_confidence: Vec<f64>,
     probability: Option<Vec<Vec<f32>>>,
+    candidates: Option<Vec<OCRCandidate>>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct OCRBatchResponse {
+    /// One result per input image, in order.
+    results: Vec<OCRResponse>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct OCRCandidate {
+    text: String,
+    score: f64,
+    char_confidence: Vec<f64>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct CalcRequest {
+    #[serde(default)]
+    image: String,
+    png_fix: Option<bool>,
+    color_filter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct CalcResponse {
+    /// Raw OCR text.
+    text: String,
+    /// Normalized expression that was evaluated.
+    expression: String,
+    value: f64,
+    /// `value` formatted for submission, without a trailing ".0".
+    result: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct ClickRequest {
+    #[serde(default)]
+    image: String,
+    /// Characters to click, in order. Either this or `prompt_image` is required.
+    prompt: Option<String>,
+    /// Base64 image of the prompt characters, read left to right.
+    prompt_image: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct ClickTargetResponse {
+    text: String,
+    x: u32,
+    y: u32,
+    bbox: Vec<u32>,
+    ocr_text: String,
+    confidence: f64,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct ClickResponse { targets: Vec<ClickTargetResponse> }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct DETRequest {
+    #[serde(default)]
+    image: String,
+    /// Registered model name, see `/models`. Defaults to the `--det-path` model.
+    model: Option<String>,
+    /// Minimum box score, default 0.1.
+    score_threshold: Option<f32>,
+    /// IoU above which overlapping boxes are merged, default 0.45.
+    nms_threshold: Option<f32>,
+    /// Return boxes as bare `[x1, y1, x2, y2]` arrays, as older versions did.
+    flat: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct DETBox { x1: u32, y1: u32, x2: u32, y2: u32, score: f32, class_id: u32 }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(untagged)]
+enum DETBoxes {
+    Structured(Vec<DETBox>),
+    Flat(Vec<Vec<u32>>),
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct DETResponse { bboxes: DETBoxes }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct SlideRequest {
+    #[serde(default)]
+    target_image: String,
+    #[serde(default)]
+    background_image: String,
+    simple_target: Option<bool>,
+    /// Number of non-overlapping candidate positions to return, best first.
+    top_n: Option<usize>,
+    /// Also return a drag trajectory to the best match. Form fields carry it
+    /// as a JSON string.
+    track: Option<TrackRequest>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
-struct DETRequest { image: String }
+struct TrackRequest {
+    /// "fast", "normal" (default) or "careful". The fields below override it.
+    preset: Option<String>,
+    seed: Option<u64>,
+    /// At most 10000.
+    duration_ms: Option<u32>,
+    /// Fraction of the distance, within [0, 1].
+    overshoot: Option<f64>,
+    /// Non-negative, in pixels.
+    jitter: Option<f64>,
+    interval_ms: Option<u32>,
+}
+
+impl TrackRequest {
+    fn options(&self) -> anyhow::Result<TrackOptions> {
+        let mut options = match &self.preset {
+            Some(v) => v.parse::<TrackPreset>()?.options(),
+            None => TrackOptions::default(),
+        };
+        options.seed = self.seed;
+        if let Some(v) = self.duration_ms { options.duration_ms = v; }
+        if let Some(v) = self.overshoot { options.overshoot = v; }
+        if let Some(v) = self.jitter { options.jitter = v; }
+        if let Some(v) = self.interval_ms { options.interval_ms = v; }
+        options.validate()?;
+        Ok(options)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct TrackPointResponse { x: i32, y: i32, t: u32 }
+
+impl From<TrackPoint> for TrackPointResponse {
+    fn from(p: TrackPoint) -> Self { Self { x: p.x, y: p.y, t: p.t } }
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
-struct DETResponse { bboxes: Vec<Vec<u32>> }
+struct SlideTrackRequest {
+    /// Drag distance in pixels; negative drags left.
+    distance: i32,
+    #[serde(flatten)]
+    options: TrackRequest,
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
-struct SlideRequest { target_image: String, background_image: String, simple_target: Option<bool> }
+struct SlideTrackResponse { track: Vec<TrackPointResponse> }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 struct SlideResponse { 
@@ -82,14 +318,74 @@ struct SlideResponse {
     #[serde(rename = "target_x")]
     target_x: u32, 
     #[serde(rename = "target_y")]
-    target_y: u32 
+    target_y: u32,
+    /// Normalized match score in [0, 1] of `target`.
+    score: f32,
+    candidates: Option<Vec<SlideCandidate>>,
+    /// Drag from the piece's start to `target`, when requested.
+    track: Option<Vec<TrackPointResponse>>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct SlideCandidate {
+    target: Vec<u32>,
+    score: f32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct SlideGapRequest {
+    #[serde(default)]
+    background_image: String,
+    /// Notch size in pixels, if known. Both must be given to take effect,
+    /// larger than 8, with a margin of more than 4 pixels on every side.
+    gap_width: Option<u32>,
+    gap_height: Option<u32>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct SlideGapResponse {
+    target: Vec<u32>,
+    score: f32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct CompareRequest {
+    #[serde(default)]
+    target_image: String,
+    #[serde(default)]
+    background_image: String,
+    /// Per-channel difference for a pixel to count as changed. Default 80.
+    diff_threshold: Option<u8>,
+    /// Minimum horizontal or vertical run of changed pixels. Default 5.
+    min_run: Option<u32>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
-struct CompareRequest { target_image: String, background_image: String }
+struct CompareResponse {
+    /// Whether a gap was found. When false, `x`/`y` are 0 and `target` is null.
+    found: bool,
+    x: u32,
+    y: u32,
+    /// Gap bounding box [x1, y1, x2, y2]; `x2`/`y2` are exclusive, as in `/slide-match`.
+    target: Option<Vec<u32>>,
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
-struct CompareResponse { x: u32, y: u32 }
+struct RotateRequest {
+    /// The rotated inner disc.
+    #[serde(default)]
+    image: String,
+    /// Background with the ring around the disc.
+    #[serde(default)]
+    background_image: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct RotateResponse {
+    /// Clockwise correction in degrees, in [0, 360).
+    angle: f64,
+    score: f64,
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 struct ToggleRequest {
@@ -102,6 +398,8 @@ struct ToggleRequest {
 struct StatusResponse {
     service_status: String,
     enabled_features: Vec<String>,
+    /// Provenance of every loaded model.
+    models: Vec<ModelInfo>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
@@ -111,66 +409,209 @@ struct APIResponse<T> {
     data: Option<T>,
 }
 
-fn load_ocr_internal() -> anyhow::Result<Ddddocr<'static>> {
+fn load_ocr_internal() -> anyhow::Result<LoadedModel> {
     let args = ARGS.get().context("Args not initialized")?;
     if !args.ocr_path.exists() {
         anyhow::bail!("OCR model not found at {:?}", args.ocr_path);
     }
-    println!("Loading OCR model from {:?}", args.ocr_path);
-    let model = std::fs::read(&args.ocr_path).context("Reading OCR model")?;
-    let mut json_path = args.ocr_path.clone();
-    json_path.set_extension("json");
+    load_ocr_from(&args.ocr_path, args.ocr_charset_range.as_deref())
+}
+
+/// `range` overrides the one in the charset JSON.
+fn load_ocr_from(path: &Path, range: Option<&str>) -> anyhow::Result<LoadedModel> {
+    println!("Loading OCR model from {:?}", path);
+    let model = std::fs::read(path).context("Reading OCR model")?;
+    check_pinned(&model)?;
+    let json_path = path.with_extension("json");
     let json_str = std::fs::read_to_string(&json_path).context("Reading OCR charset")?;
-    let charset: Charset = serde_json::from_str(&json_str)?;
+    let charset: Charset = serde_json::from_str(&json_str).with_context(|| format!("Parsing OCR charset {:?}", json_path))?;
     
-    let mut instance = Ddddocr::new(model, charset)?;
-    if let Some(range) = &args.ocr_charset_range {
-        instance.set_ranges(range.as_str());
+    let instance = build_ocr(&model, charset, range)?;
+    println!("OCR loaded (sha256 {}).", instance.sha256());
+    Ok(LoadedModel::new(instance, path))
+}
+
+fn build_ocr(model: &[u8], charset: Charset, range: Option<&str>) -> anyhow::Result<Ddddocr<'static>> {
+    let args = ARGS.get().context("Args not initialized")?;
+    let mut instance = Ddddocr::new_with_options(model, charset, args.session_options())?;
+    if let Some(range) = range {
+        instance.set_ranges(range.parse::<CharsetRange>().context("Invalid --ocr-charset-range")?);
     }
-    println!("OCR loaded.");
     Ok(instance)
 }
 
-fn load_det_internal() -> anyhow::Result<Ddddocr<'static>> {
+fn load_det_internal() -> anyhow::Result<LoadedModel> {
     let args = ARGS.get().context("Args not initialized")?;
     if !args.det_path.exists() {
         anyhow::bail!("DET model not found at {:?}", args.det_path);
     }
-    println!("Loading DET model from {:?}", args.det_path);
-    let model = std::fs::read(&args.det_path).context("Reading DET model")?;
-    let instance = Ddddocr::new_det(model)?;
-    println!("DET loaded.");
-    Ok(instance)
+    load_det_from(&args.det_path)
 }
 
-#[endpoint]
-async fn ocr(req: JsonBody<OCRRequest>) -> anyhow::Result<Json<APIResponse<OCRResponse>>> {
-    let ocr_lock = OCR.read().await;
-    let ocr_instance = ocr_lock.as_ref().context("OCR not enabled")?;
-    
-    let bytes = BASE64_STANDARD.decode(&req.image).context("Base64 decode failed")?;
-    
-    let filter = if let Some(v) = req.color_filter.clone() {
-        Some(serde_json::from_value::<ColorFilter>(v).context("Invalid color_filter format")?)
-    } else {
-        None
-    };
+fn load_det_from(path: &Path) -> anyhow::Result<LoadedModel> {
+    println!("Loading DET model from {:?}", path);
+    let model = std::fs::read(path).context("Reading DET model")?;
+    check_pinned(&model)?;
+    let instance = build_det(&model)?;
+    println!("DET loaded (sha256 {}).", instance.sha256());
+    Ok(LoadedModel::new(instance, path))
+}
 
-    let charset_range = if let Some(ref v) = req.charset_range {
-        let ocr_charset_range = match v.as_str() {
-            "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" => {
-                CharsetRange::from(v.parse::<i32>().unwrap())
+fn build_det(model: &[u8]) -> anyhow::Result<Ddddocr<'static>> {
+    let args = ARGS.get().context("Args not initialized")?;
+    Ok(Ddddocr::new_det_with_options(model, args.session_options())?)
+}
+
+/// Refuses models whose hash is not in `--pin-sha256`, if any are pinned.
+fn check_pinned(model: &[u8]) -> anyhow::Result<()> {
+    let args = ARGS.get().context("Args not initialized")?;
+    if args.pinned_sha256.is_empty() { return Ok(()); }
+    let sha = sha256::digest(model);
+    anyhow::ensure!(
+        args.pinned_sha256.iter().any(|v| v.eq_ignore_ascii_case(&sha)),
+        "Model sha256 {} is not in the pinned allowlist", sha
+    );
+    Ok(())
+}
+
+/// Replaces the model in `slot` with a fresh load from disk, if the feature
+/// is enabled both before and after the load. On failure the old instance
+/// keeps serving.
+async fn reload(slot: &RwLock<Option<Arc<LoadedModel>>>, name: &str, load: fn() -> anyhow::Result<LoadedModel>) {
+    if slot.read().await.is_none() { return; }
+    let res = spawn_blocking(move || {
+        let instance = load()?;
+        instance.validate()?;
+        Ok::<_, anyhow::Error>(instance)
+    }).await;
+    match res {
+        Ok(Ok(instance)) => {
+            let mut slot = slot.write().await;
+            // A `/toggle-feature` disable that landed during the load wins.
+            if slot.is_none() {
+                println!("{} was disabled while reloading, dropping the new model.", name);
+                return;
             }
-            v => CharsetRange::from(v),
-        };
+            *slot = Some(Arc::new(instance));
+            println!("{} reloaded.", name);
+        },
+        Ok(Err(e)) => println!("Reloading {} failed, keeping the old model: {:?}", name, e),
+        Err(e) => println!("Join error: {:?}", e),
+    }
+}
+
+/// Size and modification time of each file, to notice rewrites.
+fn fingerprint(paths: &[PathBuf]) -> Vec<Option<(u64, std::time::SystemTime)>> {
+    paths.iter()
+        .map(|p| std::fs::metadata(p).and_then(|m| Ok((m.len(), m.modified()?))).ok())
+        .collect()
+}
+
+/// Polls the default model files and reloads a model once its files have
+/// changed and then stayed unchanged for one interval, so half-written
+/// downloads are not picked up.
+async fn watch_models(interval: std::time::Duration) {
+    let Some(args) = ARGS.get() else { return };
+    let ocr_files = [args.ocr_path.clone(), args.ocr_path.with_extension("json")];
+    let det_files = [args.det_path.clone()];
+    let mut loaded = (fingerprint(&ocr_files), fingerprint(&det_files));
+    let mut last = loaded.clone();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let now = (fingerprint(&ocr_files), fingerprint(&det_files));
+        if now.0 != loaded.0 && now.0 == last.0 {
+            println!("OCR model files changed, reloading.");
+            reload(&OCR, "OCR", load_ocr_internal).await;
+            loaded.0 = now.0.clone();
+        }
+        if now.1 != loaded.1 && now.1 == last.1 {
+            println!("DET model file changed, reloading.");
+            reload(&DET, "DET", load_det_internal).await;
+            loaded.1 = now.1.clone();
+        }
+        last = now;
+    }
+}
+
+/// Loads every model in `dir`, skipping (and reporting) the ones that fail.
+fn load_model_dir(dir: &Path) -> anyhow::Result<BTreeMap<String, Arc<LoadedModel>>> {
+    let mut models = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Reading model dir {:?}", dir))? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|v| v != "onnx") { continue; }
+        let Some(name) = path.file_stem().and_then(|v| v.to_str()) else { continue };
+        let instance = if path.with_extension("json").exists() { load_ocr_from(&path, None) } else { load_det_from(&path) };
+        match instance {
+            Ok(instance) => { models.insert(name.to_string(), Arc::new(instance)); },
+            Err(e) => println!("Skipping model {:?}: {:?}", path, e),
+        }
+    }
+    Ok(models)
+}
+
+/// The named OCR model, or the default one.
+async fn ocr_model(name: Option<&str>) -> anyhow::Result<Arc<LoadedModel>> {
+    let Some(name) = name else {
+        return Ok(OCR.read().await.as_ref().ok_or(RequestError::FeatureDisabled("OCR"))?.clone());
+    };
+    let model = MODELS.read().await.get(name).cloned().ok_or_else(|| RequestError::ModelNotFound(name.to_string()))?;
+    if model.charset().is_none() {
+        return Err(RequestError::BadRequest(format!("Model {} is not an OCR model", name)).into());
+    }
+    Ok(model)
+}
+
+/// The named detection model, or the default one.
+async fn det_model(name: Option<&str>) -> anyhow::Result<Arc<LoadedModel>> {
+    let Some(name) = name else {
+        return Ok(DET.read().await.as_ref().ok_or(RequestError::FeatureDisabled("DET"))?.clone());
+    };
+    let model = MODELS.read().await.get(name).cloned().ok_or_else(|| RequestError::ModelNotFound(name.to_string()))?;
+    if model.detection_input_size().is_none() {
+        return Err(RequestError::BadRequest(format!("Model {} is not a detection model", name)).into());
+    }
+    Ok(model)
+}
+
+/// Form fields and query parameters are strings, so a list or object given
+/// as a string is read as JSON first.
+fn parse_color_filter(value: serde_json::Value) -> Result<ColorFilter, RequestError> {
+    let invalid = |e: serde_json::Error| RequestError::InvalidColorFilter(e.to_string());
+    let value = match value {
+        serde_json::Value::String(v) if v.starts_with(['[', '{']) => serde_json::from_str(&v).map_err(invalid)?,
+        v => v,
+    };
+    serde_json::from_value(value).map_err(invalid)
+}
+
+/// Request options resolved once and shared by every image of a request.
+struct ResolvedOCROptions {
+    png_fix: bool,
+    filter: Option<ColorFilter>,
+    charset_range: Option<CharsetRange>,
+    need_prob: bool,
+    top_k: usize,
+    pattern: Option<Arc<TextPattern>>,
+    decoder: CtcDecoder,
+}
+
+async fn ocr_options(ocr_instance: &Ddddocr<'static>, options: &OCROptions) -> anyhow::Result<ResolvedOCROptions> {
+    let filter = options.color_filter.clone().map(parse_color_filter).transpose()?;
+
+    let charset_range = if let Some(ref v) = options.charset_range {
+        let ocr_charset_range: CharsetRange = v.parse()?;
 
         // Use cache for calculated ranges
+        // Keyed by model hash too: preset 7 depends on the model's charset,
+        // and a name can be re-registered with another model.
+        let key = format!("{}\0{}", ocr_instance.sha256(), v);
         let mut cache = CACHE.lock().await;
-        let calculated = if let Some(cached) = cache.get(v) {
+        let calculated = if let Some(cached) = cache.get(&key) {
             cached.clone()
         } else {
             let calculated = ocr_instance.calc_ranges(ocr_charset_range);
-            cache.put(v.clone(), calculated.clone());
+            cache.put(key, calculated.clone());
             calculated
         };
         Some(CharsetRange::Charset(calculated))
@@ -178,114 +619,310 @@ async fn ocr(req: JsonBody<OCRRequest>) -> anyhow::Result<Json<APIResponse<OCRRe
         None
     };
     
-    let png_fix = req.png_fix.unwrap_or(false);
-    let need_prob = req.probability.unwrap_or(false);
-
-    // We cannot pass ocr_instance (reference) to spawn_blocking because it's not 'static.
-    // We have to run it here or clone what is needed.
-    // Ddddocr is Send+Sync. But we hold a ReadGuard.
-    // We cannot easily move the ReadGuard into spawn_blocking.
-    // However, Ddddocr is designed to be shared. 
-    // Since we can't move the reference across thread boundary if it borrows from stack (guard),
-    // we might need to change Ddddocr to be Arc-wrapped internally or wrap the whole thing in Arc.
-    // Wait, OCR is RwLock<Option<Ddddocr>>.
-    // If we want to use it in spawn_blocking, we usually need an Arc.
-    // BUT, Ddddocr methods take &self.
-    // If we run strictly cpu bound work, we can use block_in_place or just run it?
-    // No, running heavy compute in async fn blocks the executor.
-    // Ideally Ddddocr should be wrapped in Arc so we can clone the Arc and pass it to spawn_blocking.
-    // Let's do that: Change globals to Arc<Ddddocr>.
-    
-    // Actually, let's stick to the previous implementation structure where we didn't use Arc but spawn_blocking?
-    // In previous code: `let ocr_instance = OCR.get()...` returns `&Ddddocr`.
-    // `spawn_blocking(move || ...)` worked because `&Ddddocr` was from `OnceLock` which is static ref.
-    // Now we have `RwLockReadGuard`. We can't pass reference to guard to another thread easily.
-    // Best approach: Wrap Ddddocr in Arc.
-    // `static OCR: LazyLock<RwLock<Option<Arc<Ddddocr<'static>>>>>`
-    
-    // BUT, I can't change `src/lib.rs` easily right now (I can, but prefer not to unless needed).
-    // Let's verify: `Ddddocr` holds `Mutex<Session>`.
-    // If I wrap it in Arc, `Arc<Ddddocr>` is cheap to clone.
-    // So `RwLock<Option<Arc<Ddddocr>>>` is the way.
-    
-    // Refactoring to use Arc.
-    let ocr_instance = ocr_instance.clone(); // Clone the Arc
-    drop(ocr_lock); // Release lock early
-
-    let (text, probability) = spawn_blocking(move || {
-        let prob = ocr_instance.classification_probability_with_options(&bytes, png_fix, filter, charset_range)?;
-        Ok::<(String, Option<Vec<Vec<f32>>>), anyhow::Error>((
-            prob.clone().get_text().to_string(),
-            need_prob.then_some(prob.probability),
-        ))
+    let png_fix = options.png_fix.unwrap_or(false);
+    let need_prob = options.probability.unwrap_or(false);
+    let (top_k, decoder) = ocr_decoder(options)?;
+    let pattern = if let Some(ref v) = options.pattern {
+        let cached = PATTERN_CACHE.lock().await.get(v).cloned();
+        let compiled = if let Some(cached) = cached {
+            cached
+        } else {
+            // Building the DFA can take a while; keep it off the executor and
+            // out of the lock.
+            let source = v.clone();
+            let compiled = spawn_blocking(move || TextPattern::new(&source)).await.context("Task join failed")?;
+            let compiled = Arc::new(compiled.context("Invalid pattern")?);
+            PATTERN_CACHE.lock().await.put(v.clone(), compiled.clone());
+            compiled
+        };
+        Some(compiled)
+    } else {
+        None
+    };
+
+    Ok(ResolvedOCROptions { png_fix, filter, charset_range, need_prob, top_k, pattern, decoder })
+}
+
+/// Most alternatives `top_k` may ask for.
+const MAX_TOP_K: usize = 20;
+/// Widest CTC beam; beam search costs grow with the square of the width.
+const MAX_BEAM_WIDTH: usize = 64;
+
+fn ocr_decoder(options: &OCROptions) -> Result<(usize, CtcDecoder), RequestError> {
+    let top_k = options.top_k.unwrap_or(1).max(1);
+    if top_k > MAX_TOP_K {
+        return Err(RequestError::BadRequest(format!("top_k {} exceeds the limit of {}", top_k, MAX_TOP_K)));
+    }
+    if let Some(beam_width) = options.beam_width.filter(|&v| v > MAX_BEAM_WIDTH) {
+        return Err(RequestError::BadRequest(format!("beam_width {} exceeds the limit of {}", beam_width, MAX_BEAM_WIDTH)));
+    }
+    let decoder = match options.decoder {
+        Some(DecoderKind::Greedy) => CtcDecoder::Greedy,
+        None if top_k == 1 && options.beam_width.is_none() && options.pattern.is_none() => CtcDecoder::Greedy,
+        None | Some(DecoderKind::BeamSearch) => CtcDecoder::BeamSearch {
+            beam_width: options.beam_width.unwrap_or(DEFAULT_BEAM_WIDTH).max(top_k),
+        },
+    };
+    Ok((top_k, decoder))
+}
+
+fn ocr_response(prob: CharacterProbability, options: &ResolvedOCROptions) -> OCRResponse {
+    let top_k = options.top_k;
+    let candidates = match (&options.pattern, options.decoder) {
+        (Some(pattern), CtcDecoder::BeamSearch { beam_width }) => prob.decode_with_pattern(pattern, beam_width, top_k),
+        (Some(pattern), decoder @ CtcDecoder::Greedy) => prob.decode(decoder, 1).into_iter().filter(|c| pattern.is_match(&c.text)).collect(),
+        (None, decoder) => prob.decode(decoder, top_k),
+    };
+    let best = candidates.first().cloned().unwrap_or_default();
+    OCRResponse {
+        text: best.text,
+        confidence: best.score,
+        char_confidence: best.char_confidence,
+        probability: options.need_prob.then_some(prob.probability),
+        candidates: (top_k > 1).then(|| candidates.into_iter().map(|c| OCRCandidate {
+            text: c.text,
+            score: c.score,
+            char_confidence: c.char_confidence,
+        }).collect()),
+    }
+}
+
+#[endpoint]
+async fn ocr(req: ImageBody<OCRRequest>) -> AppResult<Json<APIResponse<OCRResponse>>> {
+    let ocr_instance = ocr_model(req.options.model.as_deref()).await?;
+    let bytes = req.image("image", &req.image)?;
+    let options = ocr_options(&ocr_instance, &req.options).await?;
+
+    let data = spawn_blocking(move || {
+        let prob = ocr_instance.classification_probability_with_options(&bytes, options.png_fix, options.filter.clone(), options.charset_range.clone())?;
+        Ok::<OCRResponse, anyhow::Error>(ocr_response(prob, &options))
+    }).await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(data)
+    }))
+}
+
+#[endpoint]
+async fn ocr_batch(req: ImageBody<OCRBatchRequest>) -> AppResult<Json<APIResponse<OCRBatchResponse>>> {
+    let ocr_instance = ocr_model(req.options.model.as_deref()).await?;
+
+    let max_batch = ARGS.get().context("Args not initialized")?.max_batch;
+    let count = req.images.len() + req.files.get("images").map_or(0, Vec::len);
+    if count > max_batch {
+        return Err(RequestError::BadRequest(format!("{} images exceed the batch limit of {}", count, max_batch)).into());
+    }
+    let images = req.images("images", &req.images)?;
+    if images.is_empty() {
+        return Err(RequestError::BadRequest("images must not be empty".into()).into());
+    }
+    let options = ocr_options(&ocr_instance, &req.options).await?;
+
+    let results = spawn_blocking(move || {
+        let probs = ocr_instance.classification_probability_batch(&images, options.png_fix, options.filter.clone(), options.charset_range.clone())?;
+        Ok::<Vec<OCRResponse>, anyhow::Error>(probs.into_iter().map(|p| ocr_response(p, &options)).collect())
     }).await.context("Task join failed")??;
 
     Ok(Json(APIResponse {
         code: 200, msg: "success".into(),
-        data: Some(OCRResponse {
-            text,
-            probability,
+        data: Some(OCRBatchResponse { results })
+    }))
+}
+
+#[endpoint]
+async fn calc(req: ImageBody<CalcRequest>) -> AppResult<Json<APIResponse<CalcResponse>>> {
+    let ocr_lock = OCR.read().await;
+    let ocr_instance = ocr_lock.as_ref().ok_or(RequestError::FeatureDisabled("OCR"))?.clone();
+    drop(ocr_lock);
+
+    let bytes = req.image("image", &req.image)?;
+    let filter = req.color_filter.clone().map(parse_color_filter).transpose()?;
+    let png_fix = req.png_fix.unwrap_or(false);
+
+    let res = spawn_blocking(move || ocr_instance.calculation_with_options(&bytes, png_fix, filter))
+        .await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(CalcResponse {
+            result: res.answer(),
+            text: res.text,
+            expression: res.expression,
+            value: res.value,
         })
     }))
 }
 
 #[endpoint]
-async fn det(req: JsonBody<DETRequest>) -> anyhow::Result<Json<APIResponse<DETResponse>>> {
-    let det_lock = DET.read().await;
-    let det_instance = det_lock.as_ref().context("DET not enabled")?.clone();
-    drop(det_lock);
+async fn click(req: ImageBody<ClickRequest>) -> AppResult<Json<APIResponse<ClickResponse>>> {
+    let ocr_instance = OCR.read().await.as_ref().ok_or(RequestError::FeatureDisabled("OCR"))?.clone();
+    let det_instance = DET.read().await.as_ref().ok_or(RequestError::FeatureDisabled("DET"))?.clone();
 
-    let bytes = BASE64_STANDARD.decode(&req.image).context("Base64 decode failed")?;
-    let boxes = spawn_blocking(move || det_instance.detection(&bytes)).await.context("Task join failed")??;
+    let bytes = req.image("image", &req.image)?;
+    let prompt = match (&req.prompt, req.optional_image("prompt_image", req.prompt_image.as_deref())?) {
+        (Some(text), _) => ClickPrompt::Text(text.clone()),
+        (None, Some(image)) => ClickPrompt::Image(image),
+        (None, None) => return Err(RequestError::BadRequest("Either prompt or prompt_image is required".into()).into()),
+    };
+
+    let targets = spawn_blocking(move || click_match(&ocr_instance, &det_instance, &bytes, prompt))
+        .await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(ClickResponse {
+            targets: targets.into_iter().map(|t| ClickTargetResponse {
+                text: t.text,
+                x: t.x,
+                y: t.y,
+                bbox: vec![t.bbox.x1, t.bbox.y1, t.bbox.x2, t.bbox.y2],
+                ocr_text: t.ocr_text,
+                confidence: t.confidence,
+            }).collect(),
+        })
+    }))
+}
+
+#[endpoint]
+async fn det(req: ImageBody<DETRequest>) -> AppResult<Json<APIResponse<DETResponse>>> {
+    let det_instance = det_model(req.model.as_deref()).await?;
+
+    let bytes = req.image("image", &req.image)?;
+    let defaults = det_instance.detection_options();
+    let options = DetectionOptions {
+        score_threshold: req.score_threshold.unwrap_or(defaults.score_threshold),
+        nms_threshold: req.nms_threshold.unwrap_or(defaults.nms_threshold),
+    };
+    let boxes = spawn_blocking(move || det_instance.detection_with_options(&bytes, options)).await.context("Task join failed")??;
     
     Ok(Json(APIResponse {
         code: 200, msg: "success".into(),
         data: Some(DETResponse {
-            bboxes: boxes.into_iter().map(|b| vec![b.x1, b.y1, b.x2, b.y2]).collect(),
+            bboxes: if req.flat.unwrap_or(false) {
+                DETBoxes::Flat(boxes.into_iter().map(|b| vec![b.x1, b.y1, b.x2, b.y2]).collect())
+            } else {
+                DETBoxes::Structured(boxes.into_iter().map(|b| DETBox {
+                    x1: b.x1, y1: b.y1, x2: b.x2, y2: b.y2, score: b.score, class_id: b.class_id,
+                }).collect())
+            },
         })
     }))
 }
 
 #[endpoint]
-async fn slide(req: JsonBody<SlideRequest>) -> anyhow::Result<Json<APIResponse<SlideResponse>>> {
+async fn slide(req: ImageBody<SlideRequest>) -> AppResult<Json<APIResponse<SlideResponse>>> {
     if !SLIDE_ENABLED.load(Ordering::Relaxed) {
-        return Err(anyhow::anyhow!("Slide feature is disabled").into());
+        return Err(RequestError::FeatureDisabled("Slide").into());
     }
-    let target = BASE64_STANDARD.decode(&req.target_image).context("Base64 decode failed")?;
-    let bg = BASE64_STANDARD.decode(&req.background_image).context("Base64 decode failed")?;
+    let target = req.image("target_image", &req.target_image)?;
+    let bg = req.image("background_image", &req.background_image)?;
     let simple = req.simple_target.unwrap_or(false);
+    let top_n = req.top_n.unwrap_or(1).max(1);
+    let track_options = req.track.as_ref().map(TrackRequest::options).transpose()?;
     
-    let res = spawn_blocking(move || {
-        if simple {
-            simple_slide_match(&target, &bg)
+    let (mut res, track) = spawn_blocking(move || {
+        let res = if simple {
+            simple_slide_match_candidates(&target, &bg, top_n)?
         } else {
-            slide_match(&target, &bg)
-        }
+            slide_match_candidates(&target, &bg, top_n)?
+        };
+        let track = track_options.map(|options| {
+            let distance = res[0].x1 as i32 - res[0].target_x as i32;
+            Ok::<_, DdddocrError>(generate_track(distance, &options)?.into_iter().map(Into::into).collect())
+        }).transpose()?;
+        Ok::<_, DdddocrError>((res, track))
     }).await.context("Task join failed")??;
+    let best = res.remove(0);
+    let candidates = (top_n > 1).then(|| {
+        std::iter::once(&best).chain(res.iter()).map(|b| SlideCandidate {
+            target: vec![b.x1, b.y1, b.x2, b.y2],
+            score: b.score,
+        }).collect()
+    });
     
     Ok(Json(APIResponse {
         code: 200, msg: "success".into(),
         data: Some(SlideResponse {
+            target: vec![best.x1, best.y1, best.x2, best.y2],
+            target_x: best.target_x, 
+            target_y: best.target_y,
+            score: best.score,
+            candidates,
+            track,
+        })
+    }))
+}
+
+#[endpoint]
+async fn slide_gap(req: ImageBody<SlideGapRequest>) -> AppResult<Json<APIResponse<SlideGapResponse>>> {
+    if !SLIDE_ENABLED.load(Ordering::Relaxed) {
+        return Err(RequestError::FeatureDisabled("Slide").into());
+    }
+    let bg = req.image("background_image", &req.background_image)?;
+    let size = req.gap_width.zip(req.gap_height);
+
+    let res = spawn_blocking(move || slide_gap_match(&bg, size)).await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(SlideGapResponse {
             target: vec![res.x1, res.y1, res.x2, res.y2],
-            target_x: res.target_x, 
-            target_y: res.target_y
+            score: res.score,
         })
     }))
 }
 
 #[endpoint]
-async fn compare(req: JsonBody<CompareRequest>) -> anyhow::Result<Json<APIResponse<CompareResponse>>> {
+async fn slide_track(req: JsonBody<SlideTrackRequest>) -> AppResult<Json<APIResponse<SlideTrackResponse>>> {
     if !SLIDE_ENABLED.load(Ordering::Relaxed) {
-        return Err(anyhow::anyhow!("Slide feature is disabled").into());
+        return Err(RequestError::FeatureDisabled("Slide").into());
     }
-    let target = BASE64_STANDARD.decode(&req.target_image).context("Base64 decode failed")?;
-    let bg = BASE64_STANDARD.decode(&req.background_image).context("Base64 decode failed")?;
+    let options = req.options.options()?;
+    let distance = req.distance;
+    let track = spawn_blocking(move || generate_track(distance, &options)).await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(SlideTrackResponse { track: track.into_iter().map(Into::into).collect() })
+    }))
+}
+
+#[endpoint]
+async fn compare(req: ImageBody<CompareRequest>) -> AppResult<Json<APIResponse<CompareResponse>>> {
+    if !SLIDE_ENABLED.load(Ordering::Relaxed) {
+        return Err(RequestError::FeatureDisabled("Slide").into());
+    }
+    let target = req.image("target_image", &req.target_image)?;
+    let bg = req.image("background_image", &req.background_image)?;
+    
+    let defaults = CompareOptions::default();
+    let options = CompareOptions {
+        diff_threshold: req.diff_threshold.unwrap_or(defaults.diff_threshold),
+        min_run: req.min_run.unwrap_or(defaults.min_run),
+    };
     
-    let (x, y) = spawn_blocking(move || slide_comparison(&target, &bg)).await.context("Task join failed")??;
+    let res = spawn_blocking(move || slide_comparison_with_options(&target, &bg, options)).await.context("Task join failed")??;
     
     Ok(Json(APIResponse {
         code: 200, msg: "success".into(),
-        data: Some(CompareResponse { x, y })
+        data: Some(match res {
+            Some(b) => CompareResponse { found: true, x: b.x1, y: b.y1, target: Some(vec![b.x1, b.y1, b.x2, b.y2]) },
+            None => CompareResponse { found: false, x: 0, y: 0, target: None },
+        })
+    }))
+}
+
+#[endpoint]
+async fn rotate(req: ImageBody<RotateRequest>) -> AppResult<Json<APIResponse<RotateResponse>>> {
+    let image = req.image("image", &req.image)?;
+    let bg = req.image("background_image", &req.background_image)?;
+
+    let res = spawn_blocking(move || rotate_match(&image, &bg)).await.context("Task join failed")??;
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(RotateResponse { angle: res.angle, score: res.score })
     }))
 }
 
@@ -337,6 +974,197 @@ async fn toggle_feature(req: JsonBody<ToggleRequest>) -> Json<APIResponse<()>> {
     })
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct ModelInfo {
+    /// Name to pass as `model`; null for the default `--ocr-path`/`--det-path` models.
+    name: Option<String>,
+    /// "ocr" or "det".
+    kind: String,
+    /// Number of symbols, blank included. Null for detectors.
+    charset_size: Option<usize>,
+    /// Model input shape; -1 marks a dynamic dimension.
+    input_shape: Vec<i64>,
+    /// File the model was loaded from.
+    path: String,
+    sha256: String,
+    /// Whether the hash marks a custom model, which implies ImageNet
+    /// normalization unless the charset JSON declares `mean`/`std`.
+    diy: bool,
+    /// Inference backend: "onnxruntime" or "tract".
+    backend: String,
+    /// Unix time in seconds.
+    loaded_at: u64,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct ModelsResponse { models: Vec<ModelInfo> }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+struct UploadModelRequest {
+    /// Base64 ONNX file.
+    model: String,
+    /// Charset JSON (`word`, `image`, `channel`, `charset`) for OCR models;
+    /// omit it to upload a detector.
+    charset: Option<serde_json::Value>,
+}
+
+fn model_info(name: Option<String>, model: &LoadedModel) -> ModelInfo {
+    ModelInfo {
+        name,
+        kind: if model.charset().is_some() { "ocr" } else { "det" }.to_string(),
+        charset_size: model.charset().map(|c| c.charset.len()),
+        input_shape: model.input_shape().to_vec(),
+        path: model.path.display().to_string(),
+        sha256: model.sha256().to_string(),
+        diy: model.diy(),
+        backend: if cfg!(feature = "tract") { "tract" } else { "onnxruntime" }.to_string(),
+        loaded_at: model.loaded_at.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    }
+}
+
+/// Every loaded model: the defaults first, then the registry.
+async fn loaded_models() -> Vec<ModelInfo> {
+    let mut models = Vec::new();
+    if let Some(v) = OCR.read().await.as_ref() { models.push(model_info(None, v)); }
+    if let Some(v) = DET.read().await.as_ref() { models.push(model_info(None, v)); }
+    for (name, v) in MODELS.read().await.iter() {
+        models.push(model_info(Some(name.clone()), v));
+    }
+    models
+}
+
+#[endpoint]
+async fn list_models() -> Json<APIResponse<ModelsResponse>> {
+    let models = loaded_models().await;
+
+    Json(APIResponse {
+        code: 200,
+        msg: "success".to_string(),
+        data: Some(ModelsResponse { models }),
+    })
+}
+
+/// Guards `/admin/*` with `Authorization: Bearer <--admin-token>`.
+/// Without a token the admin API is disabled.
+#[handler]
+async fn admin_auth(req: &mut Request, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let token = ARGS.get().and_then(|v| v.admin_token.as_deref());
+    let authorized = match token {
+        Some(token) => req.header::<String>("authorization")
+            .is_some_and(|v| constant_time_eq(v.as_bytes(), format!("Bearer {}", token).as_bytes())),
+        None => false,
+    };
+    if !authorized {
+        res.status_code(if token.is_none() { StatusCode::FORBIDDEN } else { StatusCode::UNAUTHORIZED });
+        ctrl.skip_rest();
+    }
+}
+
+/// Compares without stopping at the first difference, so response times do
+/// not reveal how much of the token a guess got right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Model names double as file names, so keep them to a safe alphabet.
+fn check_model_name(name: &str) -> Result<(), RequestError> {
+    if name.is_empty() || name.len() > 64 || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(RequestError::BadRequest(format!("Invalid model name: {} (use letters, digits, '_' and '-')", name)));
+    }
+    Ok(())
+}
+
+/// Where `path` is written before it is renamed into place, e.g. `a.onnx.tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+/// Writes every file to its temp path, removing them all again if one fails.
+fn stage_files(files: &[(&Path, &[u8])]) -> anyhow::Result<()> {
+    for (path, data) in files {
+        let tmp = temp_path(path);
+        if let Err(e) = std::fs::write(&tmp, data) {
+            for (path, _) in files { let _ = std::fs::remove_file(temp_path(path)); }
+            return Err(e).with_context(|| format!("Writing {:?}", tmp));
+        }
+    }
+    Ok(())
+}
+
+/// Uploads a model into `--model-dir` as `name`, replacing any model of that name.
+///
+/// The model is loaded and test-run first; if that fails, nothing on disk
+/// or in memory changes and the previous model keeps serving.
+#[endpoint]
+async fn upload_model(name: PathParam<String>, req: JsonBody<UploadModelRequest>) -> AppResult<Json<APIResponse<ModelInfo>>> {
+    let name = name.into_inner();
+    check_model_name(&name)?;
+    let dir = ARGS.get().context("Args not initialized")?.model_dir.clone().ok_or(RequestError::FeatureDisabled("Model upload (no --model-dir)"))?;
+    let req = req.into_inner();
+    let model = decode_base64("model", &req.model)?;
+    let charset = req.charset.map(serde_json::from_value::<Charset>).transpose()
+        .map_err(|e| RequestError::InvalidModel(format!("invalid charset: {}", e)))?;
+
+    let (instance, model, charset) = spawn_blocking(move || {
+        check_pinned(&model).map_err(|e| RequestError::InvalidModel(e.to_string()))?;
+        let instance = match &charset {
+            Some(v) => build_ocr(&model, v.clone(), None),
+            None => build_det(&model),
+        }.map_err(|e| RequestError::InvalidModel(format!("loading failed: {:#}", e)))?;
+        instance.validate().map_err(|e| RequestError::InvalidModel(format!("test run failed: {}", e)))?;
+        Ok::<_, anyhow::Error>((instance, model, charset))
+    }).await.context("Task join failed")??;
+
+    // Hold the write lock while files change so the registry and the directory agree.
+    let mut models = MODELS.write().await;
+    let onnx_path = dir.join(format!("{}.onnx", name));
+    let json_path = onnx_path.with_extension("json");
+    let json = charset.as_ref().map(serde_json::to_vec_pretty).transpose()?;
+    let mut files = vec![(onnx_path.as_path(), model.as_slice())];
+    files.extend(json.as_deref().map(|v| (json_path.as_path(), v)));
+    // Nothing is replaced until every file is written in full.
+    stage_files(&files)?;
+    for (path, _) in &files {
+        std::fs::rename(temp_path(path), path).with_context(|| format!("Replacing {:?}", path))?;
+    }
+    if json.is_none() && json_path.exists() {
+        std::fs::remove_file(&json_path).with_context(|| format!("Removing {:?}", json_path))?;
+    }
+    let instance = LoadedModel::new(instance, &onnx_path);
+    let info = model_info(Some(name.clone()), &instance);
+    models.insert(name.clone(), Arc::new(instance));
+    println!("Model {} uploaded to {:?}", name, onnx_path);
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: Some(info)
+    }))
+}
+
+/// Unregisters a model and deletes its files from `--model-dir`.
+#[endpoint]
+async fn delete_model(name: PathParam<String>) -> AppResult<Json<APIResponse<()>>> {
+    let name = name.into_inner();
+    check_model_name(&name)?;
+    let dir = ARGS.get().context("Args not initialized")?.model_dir.clone().ok_or(RequestError::FeatureDisabled("Model upload (no --model-dir)"))?;
+
+    let mut models = MODELS.write().await;
+    models.remove(&name).ok_or_else(|| RequestError::ModelNotFound(name.to_string()))?;
+    let onnx_path = dir.join(format!("{}.onnx", name));
+    for path in [onnx_path.with_extension("json"), onnx_path] {
+        if path.exists() {
+            std::fs::remove_file(&path).with_context(|| format!("Removing {:?}", path))?;
+        }
+    }
+
+    Ok(Json(APIResponse {
+        code: 200, msg: "success".into(),
+        data: None
+    }))
+}
+
 #[endpoint]
 async fn status() -> Json<APIResponse<StatusResponse>> {
     let mut enabled = Vec::new();
@@ -350,42 +1178,435 @@ async fn status() -> Json<APIResponse<StatusResponse>> {
         data: Some(StatusResponse {
             service_status: "running".to_string(),
             enabled_features: enabled,
+            models: loaded_models().await,
         })
     })
 }
 
+/// Failures the server detects in a request before the library sees it.
+#[derive(Debug, thiserror::Error)]
+enum RequestError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("Invalid color_filter format: {0}")]
+    InvalidColorFilter(String),
+    #[error("Invalid JSON body")]
+    InvalidJson(#[source] serde_json::Error),
+    #[error("Base64 decode failed for {field}")]
+    InvalidBase64 { field: String, source: base64::DecodeError },
+    #[error("Invalid model: {0}")]
+    InvalidModel(String),
+    #[error("Unknown model: {0}")]
+    ModelNotFound(String),
+    #[error("{0} is disabled")]
+    FeatureDisabled(&'static str),
+    #[error("{field} is not a supported image: {reason}")]
+    UnsupportedImage { field: String, reason: String },
+    #[error("Unsupported content type: {0:?}")]
+    UnsupportedMediaType(String),
+}
+
+impl RequestError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::BadRequest(_)
+            | Self::InvalidColorFilter(_)
+            | Self::InvalidJson(_)
+            | Self::InvalidBase64 { .. }
+            | Self::UnsupportedImage { .. }
+            | Self::InvalidModel(_) => StatusCode::BAD_REQUEST,
+            Self::ModelNotFound(_) => StatusCode::NOT_FOUND,
+            Self::FeatureDisabled(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest(_) => "bad_request",
+            Self::InvalidColorFilter(_) => "invalid_color",
+            Self::InvalidJson(_) => "invalid_json",
+            Self::InvalidBase64 { .. } => "invalid_base64",
+            Self::UnsupportedImage { .. } => "unsupported_image",
+            Self::InvalidModel(_) => "invalid_model",
+            Self::ModelNotFound(_) => "model_not_found",
+            Self::FeatureDisabled(_) => "feature_disabled",
+            Self::UnsupportedMediaType(_) => "unsupported_media_type",
+        }
+    }
+}
+
+/// Padding is optional: browsers and scrapers often drop it.
+const BASE64_LENIENT: GeneralPurposeConfig = GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64_STANDARD_LENIENT: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, BASE64_LENIENT);
+const BASE64_URL_SAFE_LENIENT: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, BASE64_LENIENT);
+
+/// Decodes Base64 in the standard or URL-safe alphabet, padded or not,
+/// optionally wrapped in a `data:...;base64,` URI. Whitespace is ignored.
+///
+/// The alphabets differ only in `+/` versus `-_`, so any `-` or `_` selects
+/// URL-safe; input mixing both alphabets is rejected.
+fn decode_base64(field: &str, data: &str) -> Result<Vec<u8>, RequestError> {
+    let data = data.trim();
+    let data = match data.strip_prefix("data:") {
+        Some(uri) => {
+            let (header, payload) = uri.split_once(',')
+                .ok_or_else(|| RequestError::BadRequest(format!("{} is a data URI without a comma", field)))?;
+            if !header.split(';').any(|v| v.trim().eq_ignore_ascii_case("base64")) {
+                return Err(RequestError::BadRequest(format!("{} is a data URI without ;base64", field)));
+            }
+            payload
+        },
+        None => data,
+    };
+    let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let engine = if data.contains(['-', '_']) { &BASE64_URL_SAFE_LENIENT } else { &BASE64_STANDARD_LENIENT };
+    engine.decode(data).map_err(|source| RequestError::InvalidBase64 { field: field.to_string(), source })
+}
+
+/// Rejects bytes that are not in an image format this build can read, before
+/// they reach a model.
+fn check_image(field: &str, data: Vec<u8>) -> Result<Vec<u8>, RequestError> {
+    let reason = match image::guess_format(&data) {
+        Ok(format) if format.reading_enabled() => return Ok(data),
+        Ok(format) => format!("{:?} is not supported", format),
+        Err(_) if data.is_empty() => "empty data".to_string(),
+        Err(_) => "unrecognized format".to_string(),
+    };
+    Err(RequestError::UnsupportedImage { field: field.to_string(), reason })
+}
+
+/// Form fields or query parameters, by name.
+type Fields = BTreeMap<String, Vec<String>>;
+
+/// Reads fields the way salvo reads query parameters: numbers and booleans
+/// are parsed, repeated names fill lists.
+fn parse_fields<T: serde::de::DeserializeOwned>(fields: &Fields) -> Result<T, RequestError> {
+    salvo::serde::from_str_multi_map(fields.iter().map(|(k, v)| (k.as_str(), v.iter().map(String::as_str))))
+        .map_err(|e| RequestError::BadRequest(format!("Invalid form fields: {}", e)))
+}
+
+/// A JSON request that image endpoints also accept as form fields.
+trait FormRequest: serde::de::DeserializeOwned + ToSchema {
+    /// Field a raw image body stands for, `None` if the endpoint needs two images.
+    const RAW_IMAGE: Option<&'static str>;
+
+    fn from_fields(fields: &Fields) -> Result<Self, RequestError> {
+        parse_fields(fields)
+    }
+}
+
+// `#[serde(flatten)]` hands every value over as a string, so the OCR options
+// are read on their own.
+impl FormRequest for OCRRequest {
+    const RAW_IMAGE: Option<&'static str> = Some("image");
+
+    fn from_fields(fields: &Fields) -> Result<Self, RequestError> {
+        let image = fields.get("image").and_then(|v| v.first()).cloned().unwrap_or_default();
+        Ok(Self { image, options: parse_fields(fields)? })
+    }
+}
+
+impl FormRequest for OCRBatchRequest {
+    const RAW_IMAGE: Option<&'static str> = Some("images");
+
+    fn from_fields(fields: &Fields) -> Result<Self, RequestError> {
+        let images = fields.get("images").cloned().unwrap_or_default();
+        Ok(Self { images, options: parse_fields(fields)? })
+    }
+}
+
+impl FormRequest for CalcRequest { const RAW_IMAGE: Option<&'static str> = Some("image"); }
+impl FormRequest for ClickRequest { const RAW_IMAGE: Option<&'static str> = Some("image"); }
+impl FormRequest for DETRequest { const RAW_IMAGE: Option<&'static str> = Some("image"); }
+// `track` is nested, so a form carries it as a JSON string.
+impl FormRequest for SlideRequest {
+    const RAW_IMAGE: Option<&'static str> = None;
+
+    fn from_fields(fields: &Fields) -> Result<Self, RequestError> {
+        let mut fields = fields.clone();
+        let track = fields.remove("track").and_then(|v| v.into_iter().next());
+        let mut req: Self = parse_fields(&fields)?;
+        req.track = track.map(|v| serde_json::from_str(&v))
+            .transpose()
+            .map_err(|e| RequestError::BadRequest(format!("Invalid track: {}", e)))?;
+        Ok(req)
+    }
+}
+impl FormRequest for SlideGapRequest { const RAW_IMAGE: Option<&'static str> = Some("background_image"); }
+impl FormRequest for CompareRequest { const RAW_IMAGE: Option<&'static str> = None; }
+impl FormRequest for RotateRequest { const RAW_IMAGE: Option<&'static str> = None; }
+
+/// Body of an image endpoint: JSON with Base64 images, a form with image
+/// files, or a raw image with the other fields in the query string.
+struct ImageBody<T> {
+    body: T,
+    /// Uploaded files and the raw body, by field name.
+    files: BTreeMap<String, Vec<Vec<u8>>>,
+}
+
+impl<T> std::ops::Deref for ImageBody<T> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.body }
+}
+
+impl<T: FormRequest> ImageBody<T> {
+    async fn parse(req: &mut Request) -> anyhow::Result<Self> {
+        let mime = req.content_type();
+        let kind = mime.as_ref().map(|v| (v.type_().as_str(), v.subtype().as_str()));
+        if matches!(kind, None | Some(("application", "json"))) {
+            let body = serde_json::from_slice(req.payload().await.map_err(body_error)?).map_err(RequestError::InvalidJson)?;
+            return Ok(Self { body, files: BTreeMap::new() });
+        }
+
+        let mut fields: Fields = req.queries().iter_all().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let mut files = BTreeMap::new();
+        match kind {
+            Some(("multipart", _) | ("application", "x-www-form-urlencoded")) => {
+                let form = req.form_data().await.map_err(body_error)?;
+                fields.extend(form.fields.iter_all().map(|(k, v)| (k.clone(), v.clone())));
+                for (name, parts) in form.files.iter_all() {
+                    let mut data = Vec::with_capacity(parts.len());
+                    for part in parts {
+                        data.push(tokio::fs::read(part.path()).await.context("Reading uploaded file")?);
+                    }
+                    files.insert(name.clone(), data);
+                }
+            },
+            Some(("image", _) | ("application", "octet-stream")) => {
+                let field = T::RAW_IMAGE.ok_or_else(|| RequestError::BadRequest(
+                    format!("{} takes two images, send them as multipart/form-data", req.uri().path())
+                ))?;
+                files.insert(field.to_string(), vec![req.payload().await.map_err(body_error)?.to_vec()]);
+            },
+            _ => return Err(RequestError::UnsupportedMediaType(mime.map(|v| v.to_string()).unwrap_or_default()).into()),
+        }
+        Ok(Self { body: T::from_fields(&fields)?, files })
+    }
+}
+
+fn mixed_inputs(field: &str) -> RequestError {
+    RequestError::BadRequest(format!("{} was sent both as a file and as Base64", field))
+}
+
+fn body_error(e: salvo::http::ParseError) -> RequestError {
+    RequestError::BadRequest(format!("Reading request body failed: {}", e))
+}
+
+impl<T> ImageBody<T> {
+    /// The uploaded file `field` or the Base64 `value`, checked to be an
+    /// image. Sending both is an error rather than a silent choice.
+    fn image(&self, field: &str, value: &str) -> Result<Vec<u8>, RequestError> {
+        self.optional_image(field, Some(value))?
+            .ok_or_else(|| RequestError::BadRequest(format!("{} is required", field)))
+    }
+
+    fn optional_image(&self, field: &str, value: Option<&str>) -> Result<Option<Vec<u8>>, RequestError> {
+        let files = self.files.get(field).map_or(&[][..], Vec::as_slice);
+        let data = match (files, value.filter(|v| !v.is_empty())) {
+            ([_, _, ..], _) => return Err(RequestError::BadRequest(format!("{} takes one file", field))),
+            ([_], Some(_)) => return Err(mixed_inputs(field)),
+            ([data], None) => data.clone(),
+            ([], Some(value)) => decode_base64(field, value)?,
+            ([], None) => return Ok(None),
+        };
+        check_image(field, data).map(Some)
+    }
+
+    /// The uploaded files `field`, in upload order, or the Base64 `values`.
+    /// Mixing both is an error, as their relative order would be lost.
+    fn images(&self, field: &str, values: &[String]) -> Result<Vec<Vec<u8>>, RequestError> {
+        if self.files.contains_key(field) && !values.is_empty() {
+            return Err(mixed_inputs(field));
+        }
+        let mut images = Vec::new();
+        for data in self.files.get(field).into_iter().flatten() {
+            images.push(check_image(&format!("{}[{}]", field, images.len()), data.clone())?);
+        }
+        for value in values {
+            let name = format!("{}[{}]", field, images.len());
+            images.push(check_image(&name, decode_base64(&name, value)?)?);
+        }
+        Ok(images)
+    }
+}
+
+impl<'ex, T: FormRequest + Send> salvo::extract::Extractible<'ex> for ImageBody<T> {
+    fn metadata() -> &'static salvo::extract::Metadata {
+        static METADATA: salvo::extract::Metadata = salvo::extract::Metadata::new("");
+        &METADATA
+    }
+
+    async fn extract(req: &'ex mut Request) -> Result<Self, impl Writer + Send + std::fmt::Debug + 'static> {
+        Self::parse(req).await.map_err(AppError)
+    }
+
+    async fn extract_with_arg(req: &'ex mut Request, _arg: &str) -> Result<Self, impl Writer + Send + std::fmt::Debug + 'static> {
+        Self::extract(req).await
+    }
+}
+
+impl<T: FormRequest> salvo::oapi::EndpointArgRegister for ImageBody<T> {
+    fn register(components: &mut salvo::oapi::Components, operation: &mut salvo::oapi::Operation, _arg: &str) {
+        let schema = T::to_schema(components);
+        let mut body = salvo::oapi::RequestBody::new()
+            .description("JSON with Base64 images, or form fields with the images as files.")
+            .add_content("application/json", salvo::oapi::Content::new(schema.clone()))
+            .add_content("multipart/form-data", salvo::oapi::Content::new(schema));
+        if let Some(field) = T::RAW_IMAGE {
+            let binary = salvo::oapi::Object::new()
+                .schema_type(salvo::oapi::BasicType::String)
+                .format(salvo::oapi::SchemaFormat::KnownFormat(salvo::oapi::KnownFormat::Binary));
+            body = body
+                .description(format!("JSON with Base64 images, form fields with the images as files, or the raw `{}` with the other fields in the query string.", field))
+                .add_content("image/*", salvo::oapi::Content::new(binary));
+        }
+        operation.request_body = Some(body);
+    }
+}
+
+/// HTTP status for each library error.
+fn error_status(err: &DdddocrError) -> StatusCode {
+    match err {
+        DdddocrError::ImageDecode(_)
+        | DdddocrError::Charset(_)
+        | DdddocrError::CharsetRange(_)
+        | DdddocrError::Color(_)
+        | DdddocrError::Pattern(_)
+        | DdddocrError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+        DdddocrError::SizeMismatch(_) | DdddocrError::Unrecognized(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Status and machine-readable code of a handler error, from the outermost
+/// error in its chain that we know.
+fn classify_error(err: &anyhow::Error) -> (StatusCode, &'static str) {
+    err.chain().find_map(|e| {
+        if let Some(e) = e.downcast_ref::<RequestError>() {
+            Some((e.status(), e.code()))
+        } else if let Some(e) = e.downcast_ref::<DdddocrError>() {
+            Some((error_status(e), e.code()))
+        } else if e.is::<ParseColorError>() {
+            Some((StatusCode::BAD_REQUEST, "invalid_color"))
+        } else if e.is::<ParseCharsetRangeError>() {
+            Some((StatusCode::BAD_REQUEST, "invalid_charset_range"))
+        } else {
+            None
+        }
+    }).unwrap_or((StatusCode::INTERNAL_SERVER_ERROR, "internal_server_error"))
+}
+
+/// Body of every error response.
 #[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
 struct ErrorResponse {
+    /// HTTP status code.
     code: u16,
+    /// HTTP reason phrase, e.g. "Bad Request".
     msg: String,
-    err_text: Option<String>,
+    /// Machine-readable error kind, e.g. "image_decode_failed" or "feature_disabled".
+    err_code: String,
+    /// Human-readable details.
+    err_text: String,
+    /// Same as the `X-Request-Id` response header; quote it when reporting a problem.
+    request_id: String,
 }
 
+impl ErrorResponse {
+    fn new(res: &Response, http_status: StatusCode, err_code: &str, err_text: String) -> Self {
+        Self {
+            code: http_status.as_u16(),
+            msg: http_status.canonical_reason().unwrap_or("Unknown Error").to_string(),
+            err_code: err_code.to_string(),
+            err_text,
+            request_id: res.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Error returned by endpoints; rendered as an `ErrorResponse`.
+#[derive(Debug)]
+struct AppError(anyhow::Error);
+
+type AppResult<T> = Result<T, AppError>;
+
+impl<E: Into<anyhow::Error>> From<E> for AppError {
+    fn from(e: E) -> Self { Self(e.into()) }
+}
+
+impl Scribe for AppError {
+    fn render(self, res: &mut Response) {
+        let (http_status, err_code) = classify_error(&self.0);
+        let body = ErrorResponse::new(res, http_status, err_code, format!("{:#}", self.0));
+        if http_status.is_server_error() {
+            tracing::error!(request_id = body.request_id, error = ?self.0, "request failed");
+        }
+        res.status_code(http_status);
+        res.render(Json(body));
+    }
+}
+
+impl EndpointOutRegister for AppError {
+    fn register(components: &mut salvo::oapi::Components, operation: &mut salvo::oapi::Operation) {
+        let schema = ErrorResponse::to_schema(components);
+        for (http_status, description) in [
+            (StatusCode::BAD_REQUEST, "Invalid input: bad Base64, an undecodable image or an invalid option"),
+            (StatusCode::NOT_FOUND, "The requested model is not loaded"),
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "The body is not JSON, a form or an image"),
+            (StatusCode::UNPROCESSABLE_ENTITY, "Valid input that cannot be solved, e.g. mismatched image sizes"),
+            (StatusCode::INTERNAL_SERVER_ERROR, "Model or server failure"),
+            (StatusCode::SERVICE_UNAVAILABLE, "The feature is disabled"),
+        ] {
+            operation.responses.insert(
+                http_status.as_str(),
+                salvo::oapi::Response::new(description).add_content("application/json", schema.clone()),
+            );
+        }
+    }
+}
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Tags each request with an ID, the client's own `X-Request-Id` if it
+/// sent a sane one, and echoes it in the response headers.
+#[handler]
+async fn request_id(req: &mut Request, res: &mut Response) {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let id = req.header::<String>(REQUEST_ID_HEADER)
+        .filter(|v| !v.is_empty() && v.len() <= 128 && v.bytes().all(|b| b.is_ascii_graphic()))
+        .unwrap_or_else(|| {
+            use std::hash::BuildHasher;
+            let seed = (std::time::SystemTime::now(), COUNTER.fetch_add(1, Ordering::Relaxed));
+            format!("{:016x}", std::collections::hash_map::RandomState::new().hash_one(seed))
+        });
+    if let Ok(v) = id.parse() {
+        res.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+}
+
+/// Renders errors that did not come from an endpoint, such as unknown
+/// routes, unparsable bodies or failed admin authentication.
 #[handler]
 async fn custom_catcher(res: &mut Response, ctrl: &mut FlowCtrl) {
-    if let Some(s_code) = res.status_code {
-        let code = s_code.as_u16();
-        let msg = s_code.canonical_reason().unwrap_or("Unknown Error").to_string();
-        let err_text = if code == 500 {
-            // Try to get detailed error if available? 
-            // Salvo might not expose the inner error easily in catcher, 
-            // but we can check if body is already set?
-            // For now, just generic 500.
-            Some("Internal Server Error".to_string())
-        } else {
-            None
-        };
-        
-        let error_response = ErrorResponse {
-            code,
-            msg,
-            err_text,
+    if let Some(http_status) = res.status_code {
+        let (err_code, err_text) = match &res.body {
+            salvo::http::ResBody::Error(e) => (
+                status_code_name(http_status),
+                e.cause.as_ref().map(|v| v.to_string()).or_else(|| e.detail.clone()).unwrap_or_else(|| e.brief.clone()),
+            ),
+            _ => (status_code_name(http_status), http_status.canonical_reason().unwrap_or("Unknown Error").to_string()),
         };
-        res.render(Json(error_response));
+        let body = ErrorResponse::new(res, http_status, &err_code, err_text);
+        res.render(Json(body));
         ctrl.skip_rest();
     }
 }
 
+/// "Not Found" -> "not_found".
+fn status_code_name(http_status: StatusCode) -> String {
+    http_status.canonical_reason().unwrap_or("error").to_ascii_lowercase().replace([' ', '-'], "_")
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     salvo::http::request::set_global_secure_max_size(50 * 1024 * 1024);
@@ -413,12 +1634,40 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    if let Some(dir) = &args.model_dir {
+        match load_model_dir(dir) {
+            Ok(models) => { *MODELS.write().await = models; },
+            Err(e) => println!("Init model dir warning: {:?}", e),
+        }
+    }
+
+    if args.watch_interval > 0 {
+        tokio::spawn(watch_models(std::time::Duration::from_secs(args.watch_interval)));
+    }
+
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            println!("Reloading models (SIGHUP)...");
+            reload(&OCR, "OCR", load_ocr_internal).await;
+            reload(&DET, "DET", load_det_internal).await;
+        }
+    });
+
     // Register all routes; feature flags handled inside
     let router = Router::new()
         .push(Router::with_path("ocr").post(ocr))
+        .push(Router::with_path("ocr/batch").post(ocr_batch))
+        .push(Router::with_path("calc").post(calc))
+        .push(Router::with_path("click").post(click))
         .push(Router::with_path("det").post(det))
         .push(Router::with_path("slide-match").post(slide))
+        .push(Router::with_path("slide-gap").post(slide_gap))
+        .push(Router::with_path("slide-track").post(slide_track))
         .push(Router::with_path("slide-comparison").post(compare))
+        .push(Router::with_path("rotate").post(rotate))
+        .push(Router::with_path("models").get(list_models))
+        .push(Router::with_path("admin/models/{name}").hoop(admin_auth).put(upload_model).delete(delete_model))
         .push(Router::with_path("toggle-feature").post(toggle_feature))
         .push(Router::with_path("status").get(status));
         
@@ -427,7 +1676,7 @@ async fn main() -> anyhow::Result<()> {
         .unshift(doc.into_router("/api-doc/openapi.json"))
         .unshift(SwaggerUi::new("/api-doc/openapi.json").into_router("/docs"));
 
-    let service = Service::new(router).catcher(Catcher::default().hoop(custom_catcher));
+    let service = Service::new(router).hoop(request_id).catcher(Catcher::default().hoop(custom_catcher));
 
     if args.address.starts_with("/") {
         let path = PathBuf::from(&args.address);
@@ -471,3 +1720,73 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x1 PNG, whose Base64 has `+`, `/` and padding.
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAADUlEQVR4nGNg4N8CRAAD9AGH/+Y17wAAAABJRU5ErkJggg==";
+
+    fn png() -> Vec<u8> { BASE64_STANDARD.decode(PNG).unwrap() }
+
+    fn ocr_options(top_k: Option<usize>, beam_width: Option<usize>) -> OCROptions {
+        OCROptions {
+            model: None, png_fix: None, probability: None, charset_range: None, color_filter: None,
+            decoder: None, beam_width, top_k, pattern: None,
+        }
+    }
+
+    #[test]
+    fn caps_top_k_and_beam_width() {
+        assert!(matches!(ocr_decoder(&ocr_options(Some(MAX_TOP_K), None)),
+            Ok((MAX_TOP_K, CtcDecoder::BeamSearch { beam_width: MAX_TOP_K }))));
+        assert!(matches!(ocr_decoder(&ocr_options(None, Some(MAX_BEAM_WIDTH))),
+            Ok((1, CtcDecoder::BeamSearch { beam_width: MAX_BEAM_WIDTH }))));
+        assert!(matches!(ocr_decoder(&ocr_options(Some(MAX_TOP_K + 1), None)), Err(RequestError::BadRequest(_))));
+        assert!(matches!(ocr_decoder(&ocr_options(None, Some(MAX_BEAM_WIDTH + 1))), Err(RequestError::BadRequest(_))));
+        assert!(matches!(ocr_decoder(&ocr_options(Some(usize::MAX), Some(usize::MAX))), Err(RequestError::BadRequest(_))));
+    }
+
+    #[test]
+    fn rejects_unknown_color_filter() {
+        for value in [serde_json::json!("purpel"), serde_json::json!(["red", "purpel"]), serde_json::json!(r#"["purpel"]"#)] {
+            let err = parse_color_filter(value).unwrap_err();
+            assert!(matches!(&err, RequestError::InvalidColorFilter(v) if v.contains("unknown color: purpel")), "{:?}", err);
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(matches!(parse_color_filter(serde_json::json!(r#"["red"]"#)), Ok(ColorFilter::ColorRanges(_))));
+    }
+
+    #[test]
+    fn decodes_data_uri() {
+        assert_eq!(decode_base64("image", &format!("data:image/png;base64,{}", PNG)).unwrap(), png());
+    }
+
+    #[test]
+    fn decodes_url_safe_without_padding() {
+        let url_safe = PNG.replace('+', "-").replace('/', "_");
+        assert_eq!(decode_base64("image", url_safe.trim_end_matches('=')).unwrap(), png());
+        assert_eq!(decode_base64("image", PNG.trim_end_matches('=')).unwrap(), png());
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        let wrapped = PNG.as_bytes().chunks(20).map(|v| std::str::from_utf8(v).unwrap()).collect::<Vec<_>>().join("\r\n ");
+        assert_eq!(decode_base64("image", &format!("  {}\n", wrapped)).unwrap(), png());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(matches!(decode_base64("image", "data:image/png,abc"), Err(RequestError::BadRequest(_))));
+        assert!(matches!(decode_base64("image", "data:image/png;base64"), Err(RequestError::BadRequest(_))));
+        assert!(matches!(decode_base64("image", "ab+c-d"), Err(RequestError::InvalidBase64 { .. })));
+        assert!(matches!(decode_base64("image", "!!!"), Err(RequestError::InvalidBase64 { .. })));
+    }
+
+    #[test]
+    fn checks_image_format() {
+        assert!(check_image("image", png()).is_ok());
+        assert!(matches!(check_image("image", b"hello".to_vec()), Err(RequestError::UnsupportedImage { .. })));
+        assert!(matches!(check_image("image", Vec::new()), Err(RequestError::UnsupportedImage { .. })));
+    }
+}
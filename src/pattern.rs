@@ -0,0 +1,113 @@
+use regex_automata::dfa::{dense, Automaton};
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::start;
+use regex_automata::Anchored;
+
+/// Memory, in bytes, the DFA and its construction may each use. Patterns
+/// such as `(a|b)*a(a|b){20}` grow exponentially when determinized and are
+/// rejected instead.
+const DFA_SIZE_LIMIT: usize = 2 << 20;
+
+/// A regular expression the decoded text must satisfy, e.g. `^[0-9]{4}$`.
+///
+/// Matching follows `regex::Regex::is_match`, so unanchored patterns only
+/// need to occur somewhere in the text. Anchor both ends for fixed formats.
+#[derive(Debug, Clone)]
+pub struct TextPattern {
+    regex: regex::Regex,
+    dfa: dense::DFA<Vec<u32>>,
+    start: StateID,
+}
+
+/// Progress of a partially decoded string through the pattern's DFA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PatternState {
+    id: StateID,
+    matched: bool,
+}
+
+impl TextPattern {
+    pub fn new(pattern: &str) -> crate::error::Result<Self> {
+        let invalid = |e: &dyn std::fmt::Display| crate::DdddocrError::Pattern(e.to_string());
+        let regex = regex::Regex::new(pattern).map_err(|e| invalid(&e))?;
+        let dfa = dense::Builder::new()
+            .configure(dense::Config::new().dfa_size_limit(Some(DFA_SIZE_LIMIT)).determinize_size_limit(Some(DFA_SIZE_LIMIT)))
+            .build(pattern)
+            .map_err(|e| invalid(&e))?;
+        let start = dfa.start_state(&start::Config::new().anchored(Anchored::No)).map_err(|e| invalid(&e))?;
+        Ok(Self { regex, dfa, start })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    pub(crate) fn start(&self) -> PatternState {
+        PatternState { id: self.start, matched: false }
+    }
+
+    /// Feeds `symbol` to the DFA. Returns `None` once no continuation can match.
+    pub(crate) fn advance(&self, state: PatternState, symbol: &str) -> Option<PatternState> {
+        if state.matched {
+            return Some(state);
+        }
+        let mut id = state.id;
+        for &byte in symbol.as_bytes() {
+            id = self.dfa.next_state(id, byte);
+            // Matches are reported one byte late, so this covers the text before `byte`.
+            if self.dfa.is_match_state(id) {
+                return Some(PatternState { id, matched: true });
+            }
+            if self.dfa.is_dead_state(id) || self.dfa.is_quit_state(id) {
+                return None;
+            }
+        }
+        Some(PatternState { id, matched: false })
+    }
+
+    /// Whether the text that led to `state` satisfies the pattern.
+    pub(crate) fn accepts(&self, state: PatternState) -> bool {
+        state.matched || self.dfa.is_match_state(self.dfa.next_eoi_state(state.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepts(pattern: &TextPattern, text: &str) -> bool {
+        let state = pattern.advance(pattern.start(), text);
+        state.is_some_and(|v| pattern.accepts(v))
+    }
+
+    #[test]
+    fn matches_like_regex() {
+        let pattern = TextPattern::new("^[0-9]{4}$").unwrap();
+        assert!(pattern.is_match("2024"));
+        assert!(accepts(&pattern, "2024"));
+        assert!(!pattern.is_match("20a4"));
+        assert!(!accepts(&pattern, "20a4"));
+        assert!(!accepts(&pattern, "202"));
+    }
+
+    #[test]
+    fn dead_prefix_stops_early() {
+        let pattern = TextPattern::new("^ab").unwrap();
+        assert!(pattern.advance(pattern.start(), "x").is_none());
+        assert!(accepts(&pattern, "abc"));
+    }
+
+    #[test]
+    fn rejects_invalid_pattern() {
+        assert!(matches!(TextPattern::new("(a"), Err(crate::DdddocrError::Pattern(_))));
+    }
+
+    #[test]
+    fn rejects_oversized_dfa() {
+        assert!(matches!(TextPattern::new("(a|b)*a(a|b){20}"), Err(crate::DdddocrError::Pattern(_))));
+    }
+}
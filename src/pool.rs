@@ -0,0 +1,51 @@
+use ort::session::Session;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SessionOptions {
+    /// Number of sessions per model. Each runs one inference at a time and
+    /// holds its own copy of the weights.
+    pub pool_size: usize,
+    /// Intra-op threads per session. `None` keeps the backend default.
+    pub intra_threads: Option<usize>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self { Self { pool_size: 1, intra_threads: None } }
+}
+
+/// Sessions of one model that concurrent calls can run on in parallel.
+pub(crate) struct SessionPool {
+    sessions: Vec<Mutex<Session>>,
+    next: AtomicUsize,
+}
+
+impl SessionPool {
+    pub(crate) fn new(model: &[u8], options: &SessionOptions) -> crate::error::Result<Self> {
+        let sessions = (0..options.pool_size.max(1)).map(|_| {
+            let mut builder = Session::builder()?;
+            if let Some(threads) = options.intra_threads {
+                builder = builder.with_intra_threads(threads)?;
+            }
+            Ok(Mutex::new(builder.commit_from_memory(model)?))
+        }).collect::<crate::error::Result<Vec<_>>>()?;
+        Ok(Self { sessions, next: AtomicUsize::new(0) })
+    }
+
+    /// Takes the first idle session, round-robin, or waits for one if all are busy.
+    pub(crate) fn get(&self) -> MutexGuard<'_, Session> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.sessions.len();
+        for i in 0..self.sessions.len() {
+            if let Ok(session) = self.sessions[(start + i) % self.sessions.len()].try_lock() {
+                return session;
+            }
+        }
+        // A panic mid-inference leaves the session itself usable.
+        self.sessions[start].lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.sessions.len()
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{ensure, Result};
+
+/// Angular resolution of the search, in samples per full turn.
+const SAMPLES: usize = 360;
+/// Distance in pixels from the disc edge at which both sides are sampled,
+/// enough to step over anti-aliasing and drop shadows.
+const BORDER_OFFSET: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct RotateResult {
+    /// Clockwise rotation in degrees, in `[0, 360)`, that puts the image upright.
+    pub angle: f64,
+    /// Match quality in `[0, 1]`; higher is better.
+    pub score: f64,
+}
+
+/// RGB colour on the circle of radius `r` around `(cx, cy)`, or `None`
+/// where the circle leaves the image or hits a transparent pixel.
+fn sample_circle(image: &image::RgbaImage, cx: f32, cy: f32, r: f32) -> Vec<Option<[f32; 3]>> {
+    (0..SAMPLES).map(|i| {
+        let phi = i as f32 / SAMPLES as f32 * std::f32::consts::TAU;
+        let x = (cx + r * phi.cos()).round();
+        let y = (cy + r * phi.sin()).round();
+        if x < 0.0 || y < 0.0 || x >= image.width() as f32 || y >= image.height() as f32 {
+            return None;
+        }
+        let p = image.get_pixel(x as u32, y as u32);
+        (p[3] > 0).then(|| [p[0] as f32, p[1] as f32, p[2] as f32])
+    }).collect()
+}
+
+/// Rotation of a circular `inner` image so that it continues the ring of `bg`.
+///
+/// The disc is assumed centred in `bg` at the same scale, with the hole's
+/// radius equal to half the inner image's shorter side. Colours just inside
+/// the disc edge are compared to those just outside the hole at every
+/// rotation, and the best continuity wins.
+pub fn rotate_match<I1, I2>(inner: I1, bg: I2) -> Result<RotateResult>
+where I1: AsRef<[u8]>, I2: AsRef<[u8]> {
+    let inner = image::load_from_memory(inner.as_ref())?.to_rgba8();
+    let bg = image::load_from_memory(bg.as_ref())?.to_rgba8();
+    let radius = inner.width().min(inner.height()) as f32 / 2.0;
+    ensure!(radius > BORDER_OFFSET * 2.0, SizeMismatch, "inner image too small");
+    ensure!(bg.width() as f32 >= radius * 2.0 && bg.height() as f32 >= radius * 2.0, SizeMismatch, "bg too small");
+
+    let a = sample_circle(&inner, inner.width() as f32 / 2.0, inner.height() as f32 / 2.0, radius - BORDER_OFFSET);
+    let b = sample_circle(&bg, bg.width() as f32 / 2.0, bg.height() as f32 / 2.0, radius + BORDER_OFFSET);
+
+    let mut best = (0, f32::MAX);
+    for shift in 0..SAMPLES {
+        // Rotating the disc clockwise by `shift` moves its sample at phi to phi + shift.
+        let (mut cost, mut n) = (0.0, 0);
+        for (i, outer) in b.iter().enumerate() {
+            if let (Some(p), Some(q)) = (a[(i + SAMPLES - shift) % SAMPLES], outer) {
+                cost += (p[0] - q[0]).abs() + (p[1] - q[1]).abs() + (p[2] - q[2]).abs();
+                n += 1;
+            }
+        }
+        if n == 0 { continue; }
+        let cost = cost / (n as f32 * 3.0);
+        if cost < best.1 { best = (shift, cost); }
+    }
+    ensure!(best.1 < f32::MAX, Unrecognized, "no overlapping border pixels");
+
+    Ok(RotateResult {
+        angle: best.0 as f64 * 360.0 / SAMPLES as f64,
+        score: (1.0 - best.1 as f64 / 255.0).clamp(0.0, 1.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    /// A colour that differs at every angle, so only one rotation lines up.
+    fn colour(phi: f32) -> image::Rgba<u8> {
+        let c = |v: f32| (127.5 + 127.0 * v) as u8;
+        image::Rgba([c(phi.cos()), c(phi.sin()), c((2.0 * phi).cos()), 255])
+    }
+
+    fn png(image: image::RgbaImage) -> Vec<u8> {
+        let mut buf = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut buf, image::ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    /// A 60 px disc turned counter-clockwise by `degrees`, and its ring.
+    fn captcha(degrees: f32) -> (Vec<u8>, Vec<u8>) {
+        let turn = degrees.to_radians();
+        let inner = image::RgbaImage::from_fn(60, 60, |x, y| {
+            let (dx, dy) = (x as f32 + 0.5 - 30.0, y as f32 + 0.5 - 30.0);
+            if dx.hypot(dy) > 30.0 { return image::Rgba([0, 0, 0, 0]); }
+            colour(dy.atan2(dx) + turn)
+        });
+        let bg = image::RgbaImage::from_fn(100, 100, |x, y| {
+            let (dx, dy) = (x as f32 + 0.5 - 50.0, y as f32 + 0.5 - 50.0);
+            colour(dy.atan2(dx).rem_euclid(TAU))
+        });
+        (png(inner), png(bg))
+    }
+
+    fn angle_diff(a: f64, b: f64) -> f64 {
+        let d = (a - b).rem_euclid(360.0);
+        d.min(360.0 - d)
+    }
+
+    #[test]
+    fn ring_match_recovers_full_circle_angles() {
+        for degrees in [0.0, 37.0, 135.0, 260.0] {
+            let (inner, bg) = captcha(degrees);
+            let res = rotate_match(&inner, &bg).unwrap();
+            assert!(angle_diff(res.angle, degrees as f64) <= 2.0, "{} -> {:?}", degrees, res);
+            assert!(res.score > 0.9, "{:?}", res);
+        }
+    }
+
+    #[test]
+    fn ring_match_rejects_tiny_disc() {
+        let (_, bg) = captcha(0.0);
+        let tiny = png(image::RgbaImage::new(4, 4));
+        assert!(matches!(rotate_match(&tiny, &bg), Err(crate::DdddocrError::SizeMismatch(_))));
+    }
+}
@@ -0,0 +1,210 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackPreset {
+    Fast,
+    Normal,
+    Careful,
+}
+
+impl TrackPreset {
+    pub fn options(self) -> TrackOptions {
+        match self {
+            TrackPreset::Fast => TrackOptions { duration_ms: 450, overshoot: 0.03, jitter: 1.0, interval_ms: 10, seed: None },
+            TrackPreset::Normal => TrackOptions { duration_ms: 850, overshoot: 0.06, jitter: 1.5, interval_ms: 15, seed: None },
+            TrackPreset::Careful => TrackOptions { duration_ms: 1400, overshoot: 0.04, jitter: 2.0, interval_ms: 20, seed: None },
+        }
+    }
+}
+
+impl std::str::FromStr for TrackPreset {
+    type Err = crate::DdddocrError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fast" => Ok(Self::Fast),
+            "normal" => Ok(Self::Normal),
+            "careful" => Ok(Self::Careful),
+            _ => crate::error::bail!(InvalidArgument, "unknown track preset: {} (expected fast, normal or careful)", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct TrackOptions {
+    /// Approximate time of the main move, before the overshoot correction.
+    pub duration_ms: u32,
+    /// Overshoot as a fraction of the distance; `0.0` stops exactly on target.
+    pub overshoot: f64,
+    /// Amplitude of the vertical wobble in pixels.
+    pub jitter: f64,
+    /// Mean time between points.
+    pub interval_ms: u32,
+    /// Same seed, same track. `None` seeds from the clock.
+    pub seed: Option<u64>,
+}
+
+impl Default for TrackOptions {
+    fn default() -> Self { TrackPreset::Normal.options() }
+}
+
+/// Longest main move `TrackOptions::validate` accepts. With `interval_ms`
+/// at 1 this still keeps a track to about 15k points.
+pub const MAX_TRACK_DURATION_MS: u32 = 10_000;
+
+impl TrackOptions {
+    /// Rejects options that make no sense for a drag or would build an
+    /// unbounded number of points.
+    pub fn validate(&self) -> crate::error::Result<()> {
+        use crate::error::ensure;
+        ensure!(self.duration_ms <= MAX_TRACK_DURATION_MS, InvalidArgument, "duration_ms must be at most {}", MAX_TRACK_DURATION_MS);
+        ensure!(self.interval_ms >= 1, InvalidArgument, "interval_ms must be at least 1");
+        ensure!(self.overshoot.is_finite() && (0.0..=1.0).contains(&self.overshoot), InvalidArgument, "overshoot must be within [0, 1]");
+        ensure!(self.jitter.is_finite() && self.jitter >= 0.0, InvalidArgument, "jitter must be a non-negative number");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TrackPoint {
+    /// Offset from the drag start, in pixels.
+    pub x: i32,
+    pub y: i32,
+    /// Milliseconds since the mouse went down.
+    pub t: u32,
+}
+
+/// SplitMix64: tiny, and its output never changes between releases,
+/// so a seed keeps reproducing the same track.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[lo, hi)`.
+    fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * (hi - lo)
+    }
+}
+
+/// Minimum-jerk position profile, the classic model of a human reaching
+/// movement, skewed so that peak speed comes a little early.
+fn ease(p: f64) -> f64 {
+    let p = p.clamp(0.0, 1.0).powf(0.8);
+    p * p * p * (10.0 - 15.0 * p + 6.0 * p * p)
+}
+
+/// Generates a human-like drag of `distance` pixels (negative drags left).
+///
+/// The pointer accelerates and decelerates along a minimum-jerk curve, may
+/// overshoot and pull back, wobbles vertically and is sampled at irregular
+/// intervals. The last point is always exactly `(distance, y)`.
+pub fn generate_track(distance: i32, options: &TrackOptions) -> crate::error::Result<Vec<TrackPoint>> {
+    options.validate()?;
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+    });
+    let mut rng = Rng(seed);
+    let sign = if distance < 0 { -1.0 } else { 1.0 };
+    let target = distance.unsigned_abs() as f64;
+    let interval = options.interval_ms as f64;
+
+    let mut points = vec![TrackPoint { x: 0, y: 0, t: 0 }];
+    if distance == 0 {
+        return Ok(points);
+    }
+
+    let overshoot = (target * options.overshoot * rng.range(0.5, 1.5)).round();
+    let peak = target + overshoot;
+    let duration = options.duration_ms.max(1) as f64 * rng.range(0.85, 1.15);
+
+    let mut t = 0.0;
+    let mut y = 0.0;
+    let mut last_x = 0.0;
+    let push = |points: &mut Vec<TrackPoint>, x: f64, t: f64, y: f64| {
+        points.push(TrackPoint { x: (sign * x).round() as i32, y: y.round() as i32, t: t.round() as u32 });
+    };
+
+    // Main move, never stepping backwards.
+    while t < duration {
+        t += interval * rng.range(0.7, 1.3);
+        let x = (peak * ease(t / duration) + rng.range(-0.4, 0.4)).max(last_x).min(peak);
+        y = (y + rng.range(-0.35, 0.35) * options.jitter).clamp(-2.0 * options.jitter, 2.0 * options.jitter);
+        last_x = x;
+        push(&mut points, x, t.min(duration), y);
+    }
+    t = duration;
+
+    // Pull back from the overshoot.
+    if overshoot > 0.0 {
+        t += rng.range(40.0, 120.0);
+        let back = rng.range(120.0, 260.0);
+        let start = t;
+        while t < start + back {
+            t += interval * rng.range(0.8, 1.4);
+            let p = ((t - start) / back).min(1.0);
+            let x = peak - overshoot * (3.0 * p * p - 2.0 * p * p * p);
+            y += rng.range(-0.15, 0.15) * options.jitter;
+            push(&mut points, x, t, y);
+        }
+    }
+
+    // Settle on the target before releasing.
+    t += rng.range(50.0, 150.0);
+    push(&mut points, target, t, y);
+    points.dedup_by(|b, a| a.x == b.x && a.y == b.y && a.t == b.t);
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded(preset: TrackPreset, seed: u64) -> TrackOptions {
+        TrackOptions { seed: Some(seed), ..preset.options() }
+    }
+
+    #[test]
+    fn same_seed_same_track() {
+        let options = seeded(TrackPreset::Normal, 42);
+        assert_eq!(generate_track(180, &options).unwrap(), generate_track(180, &options).unwrap());
+        assert_ne!(generate_track(180, &options).unwrap(), generate_track(180, &seeded(TrackPreset::Normal, 43)).unwrap());
+    }
+
+    #[test]
+    fn ends_on_distance_with_increasing_time() {
+        for preset in [TrackPreset::Fast, TrackPreset::Normal, TrackPreset::Careful] {
+            for (seed, distance) in [(1, 1), (2, 57), (3, 240), (4, -130)] {
+                let track = generate_track(distance, &seeded(preset, seed)).unwrap();
+                assert_eq!(track[0], TrackPoint { x: 0, y: 0, t: 0 });
+                assert_eq!(track.last().unwrap().x, distance, "{:?} {}", preset, seed);
+                assert!(track.windows(2).all(|v| v[0].t <= v[1].t), "{:?}", track);
+            }
+        }
+        assert_eq!(generate_track(0, &TrackOptions::default()).unwrap(), vec![TrackPoint { x: 0, y: 0, t: 0 }]);
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        let base = seeded(TrackPreset::Normal, 1);
+        for options in [
+            TrackOptions { duration_ms: MAX_TRACK_DURATION_MS + 1, ..base },
+            TrackOptions { duration_ms: u32::MAX, interval_ms: 1, ..base },
+            TrackOptions { interval_ms: 0, ..base },
+            TrackOptions { overshoot: -0.1, ..base },
+            TrackOptions { overshoot: 1.5, ..base },
+            TrackOptions { overshoot: f64::NAN, ..base },
+            TrackOptions { jitter: -1.0, ..base },
+            TrackOptions { jitter: f64::NAN, ..base },
+            TrackOptions { jitter: f64::INFINITY, ..base },
+        ] {
+            assert!(matches!(options.validate(), Err(crate::DdddocrError::InvalidArgument(_))), "{:?}", options);
+            assert!(matches!(generate_track(100, &options), Err(crate::DdddocrError::InvalidArgument(_))), "{:?}", options);
+        }
+        assert!(TrackOptions { duration_ms: MAX_TRACK_DURATION_MS, interval_ms: 1, ..base }.validate().is_ok());
+    }
+}
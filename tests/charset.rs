@@ -0,0 +1,39 @@
+use ddddocr_musl::Charset;
+
+fn load(name: &str) -> Result<Charset, serde_json::Error> {
+    let path = format!("{}/tests/fixtures/charset/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn known_variants_load_the_same() {
+    for name in ["ddddocr", "dddd_trainer", "resize", "config_style"] {
+        let charset = load(name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert!(!charset.word, "{}", name);
+        assert_eq!(charset.image, [-1, 64], "{}", name);
+        assert_eq!(charset.channel, 1, "{}", name);
+        assert_eq!(charset.charset, ["", "a", "b", "c", "1", "2"], "{}", name);
+    }
+}
+
+#[test]
+fn joined_charset_is_split_into_symbols() {
+    let charset = load("joined_string").unwrap();
+    assert_eq!(charset.charset, ["", "a", "b", "c", "1", "2"]);
+    assert!(!charset.word);
+}
+
+#[test]
+fn missing_fields_are_named() {
+    let err = load("missing_charset").unwrap_err().to_string();
+    assert!(err.contains("missing `charset`"), "{}", err);
+    let err = load("missing_image").unwrap_err().to_string();
+    assert!(err.contains("missing `image`"), "{}", err);
+}
+
+#[test]
+fn saved_charset_uses_the_ddddocr_keys() {
+    let json = serde_json::to_value(load("resize").unwrap()).unwrap();
+    assert_eq!(json["image"], serde_json::json!([-1, 64]));
+    assert!(json.get("resize").is_none());
+}